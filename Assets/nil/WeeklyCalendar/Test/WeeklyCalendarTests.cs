@@ -190,5 +190,170 @@ namespace Tests
                 ["hide"] = true,
             }, scheduled);
         }
+
+        [Test]
+        public void GetEventTimeForDate_WhenScheduledForEveryFewWeeks_ReturnsScheduled()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["start_date"] = 1683432000.0,
+                ["every"] = 2.0,
+            }, DateTime.Parse("2023-05-21"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-21T20:30:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary(), scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenNotScheduledForEveryFewWeeks_ReturnsHidden()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["start_date"] = 1683432000.0,
+                ["every"] = 2.0,
+            }, DateTime.Parse("2023-05-14"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-14T20:30:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary
+            {
+                ["hide"] = true,
+            }, scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenSpecialDate_ReturnsSpecialDate()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["dates"] = new DataList
+                {
+                    new DataDictionary
+                    {
+                        ["date"] = "2023-05-22",
+                        ["start"] = 1200.0,
+                        ["duration"] = 60.0,
+                    },
+                },
+            }, DateTime.Parse("2023-05-22"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-22T20:00:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary
+            {
+                ["date"] = "2023-05-22",
+                ["start"] = 1200.0,
+                ["duration"] = 60.0,
+                ["override"] = true,
+            }, scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenNotSpecialDate_ReturnsNotScheduled()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["dates"] = new DataList
+                {
+                    new DataDictionary
+                    {
+                        ["date"] = "2023-05-22",
+                    },
+                },
+            }, DateTime.Parse("2023-05-21"), "America/New_York", out _, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.IsNull(scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenRescheduledToDate_ReturnsRescheduled()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["reschedule"] = new DataList
+                {
+                    new DataDictionary
+                    {
+                        ["date"] = "2023-05-21",
+                        ["to"] = "2023-05-22",
+                        ["start"] = 1200.0,
+                        ["duration"] = 90.0,
+                    },
+                },
+            }, DateTime.Parse("2023-05-22"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-22T20:00:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary
+            {
+                ["date"] = "2023-05-21",
+                ["to"] = "2023-05-22",
+                ["start"] = 1200.0,
+                ["duration"] = 90.0,
+                ["override"] = true,
+            }, scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenRescheduledFromDate_ReturnsNotScheduled()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["reschedule"] = new DataList
+                {
+                    new DataDictionary
+                    {
+                        ["date"] = "2023-05-21",
+                        ["to"] = "2023-05-22",
+                        ["start"] = 1200.0,
+                        ["duration"] = 90.0,
+                    },
+                },
+            }, DateTime.Parse("2023-05-21"), "America/New_York", out _, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.IsNull(scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenScheduledForLastWeek_ReturnsScheduled()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["weeks"] = new DataList {
+                    -1.0,
+                },
+            }, DateTime.Parse("2023-05-28"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-28T20:30:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary(), scheduled);
+        }
+
+        [Test]
+        public void GetEventTimeForDate_WhenNotScheduledForLastWeek_ReturnsHidden()
+        {
+            var success = GetCalendar().GetEventTimeForDate(new DataDictionary
+            {
+                ["start"] = 1230.0,
+                ["sunday"] = new DataDictionary(),
+                ["weeks"] = new DataList {
+                    -1.0,
+                },
+            }, DateTime.Parse("2023-05-21"), "America/New_York", out var time, out var scheduled);
+            Assert.IsTrue(success);
+            Assert.AreEqual(DateTimeOffset.Parse("2023-05-21T20:30:00.0000000-04:00"), time);
+            Assert.AreEqual(new DataDictionary
+            {
+                ["hide"] = true,
+            }, scheduled);
+        }
     }
 }
@@ -65,6 +65,7 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
 
     public VRCUrl Source;
     public VRCUrl[] Images;
+    public VRCUrl[] AtlasImages;
 
     public string Data;
     [NonSerialized]
@@ -122,6 +123,13 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
     public string ActiveLink;
 
     VRCImageDownloader downloader;
+    // Atlas pages are kept after they're downloaded because they have many posters.
+    [NonSerialized]
+    Texture2D[] atlasPages;
+    // The URL of the poster being shown, so posters that finish loading after another event is
+    // selected aren't shown.
+    [NonSerialized]
+    string posterUrl;
 
     DateTime lastUpdateStart;
     public long LastUpdatedUtc;
@@ -191,6 +199,9 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
 
     void InitFromJson(string result, bool fresh, DateTimeOffset now)
     {
+        // The posters in the atlas may have moved.
+        atlasPages = new Texture2D[AtlasImages == null ? 0 : AtlasImages.Length];
+
         if (!VRCJson.TryDeserializeFromJson(result, out var data))
         {
             LogError($"Invalid data: {data}");
@@ -435,7 +446,9 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
         DetailsTitleFill.sprite = Focus;
         DetailsTitleText.text = SelectedEvent;
 
-        if (evt.TryGetValue("poster", TokenType.DataDictionary, out token))
+        // Worlds built before the data was updated may not have a URL for the poster's number.
+        if (evt.TryGetValue("poster", TokenType.DataDictionary, out token) &&
+            (int)token.DataDictionary["n"].Double < Images.Length)
         {
             var poster = token.DataDictionary;
 
@@ -454,17 +467,42 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
                 layout.preferredWidth = layout.minWidth = DetailsPosterMaxSize.x;
                 layout.preferredHeight = layout.minHeight = height * DetailsPosterMaxSize.x / width;
             }
-            DetailsPosterStatus.gameObject.SetActive(true);
-            DetailsPosterStatus.text = StatusLoading;
-
-            var url = Images[(int)poster["n"].Double];
             var renderer = ((RawImage)DetailsPoster.GetComponent(typeof(RawImage)));
-            renderer.texture = Texture2D.whiteTexture;
-            var textureInfo = new TextureInfo();
-            textureInfo.GenerateMipMaps = true;
-            textureInfo.WrapModeU = TextureWrapMode.Clamp;
-            textureInfo.WrapModeV = TextureWrapMode.Clamp;
-            downloader.DownloadImage(url, renderer.material, (IUdonEventReceiver)this, textureInfo);
+            var url = Images[(int)poster["n"].Double];
+            Texture2D cached = null;
+            if (poster.TryGetValue("a", TokenType.DataDictionary, out var atlast) &&
+                (int)atlast.DataDictionary["p"].Double < atlasPages.Length)
+            {
+                var atlas = atlast.DataDictionary;
+                var page = (int)atlas["p"].Double;
+                var rect = atlas["r"].DataList;
+                renderer.uvRect = new Rect((float)rect[0].Double, (float)rect[1].Double, (float)rect[2].Double, (float)rect[3].Double);
+                url = AtlasImages[page];
+                cached = atlasPages[page];
+            }
+            else
+            {
+                renderer.uvRect = new Rect(0, 0, 1, 1);
+            }
+            posterUrl = url.Get();
+
+            if (cached != null)
+            {
+                DetailsPosterStatus.gameObject.SetActive(false);
+                renderer.texture = cached;
+            }
+            else
+            {
+                DetailsPosterStatus.gameObject.SetActive(true);
+                DetailsPosterStatus.text = StatusLoading;
+
+                renderer.texture = Texture2D.whiteTexture;
+                var textureInfo = new TextureInfo();
+                textureInfo.GenerateMipMaps = true;
+                textureInfo.WrapModeU = TextureWrapMode.Clamp;
+                textureInfo.WrapModeV = TextureWrapMode.Clamp;
+                downloader.DownloadImage(url, renderer.material, (IUdonEventReceiver)this, textureInfo);
+            }
         }
         else
         {
@@ -601,6 +639,10 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
     public override void OnImageLoadError(IVRCImageDownload result)
     {
         Debug.LogError($"Poster load error: {result.ErrorMessage}");
+        if (result.Url.Get() != posterUrl)
+        {
+            return;
+        }
 
         DetailsPosterStatus.gameObject.SetActive(true);
         DetailsPosterStatus.text = result.ErrorMessage;
@@ -608,6 +650,19 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
 
     public override void OnImageLoadSuccess(IVRCImageDownload result)
     {
+        var url = result.Url.Get();
+        for (var i = 0; i < atlasPages.Length; i++)
+        {
+            if (AtlasImages[i].Get() == url)
+            {
+                atlasPages[i] = result.Result;
+            }
+        }
+        if (url != posterUrl)
+        {
+            return;
+        }
+
         DetailsPosterStatus.gameObject.SetActive(false);
 
         var renderer = ((RawImage)DetailsPoster.GetComponent(typeof(RawImage)));
@@ -721,6 +776,26 @@ public partial class WeeklyCalendar : UdonSharpBehaviour
 [CustomEditor(typeof(WeeklyCalendar))]
 public class WeeklyCalendarEditor : Editor
 {
+    // This must match MAX_POSTERS in the compiler.
+    const int MaxPosters = 4096;
+    // This must match MAX_PAGES in the compiler's atlas module.
+    const int MaxAtlasPages = 16;
+
+    static void UpdateImages(WeeklyCalendar behavior)
+    {
+        var source = new Uri(behavior.Source.Get());
+        behavior.Images = new VRCUrl[MaxPosters];
+        for (var i = 0; i < MaxPosters; i++)
+        {
+            behavior.Images[i] = new VRCUrl(new Uri(source, $"posters/{i:x2}").ToString());
+        }
+        behavior.AtlasImages = new VRCUrl[MaxAtlasPages];
+        for (var i = 0; i < MaxAtlasPages; i++)
+        {
+            behavior.AtlasImages[i] = new VRCUrl(new Uri(source, $"atlas/{i:x2}").ToString());
+        }
+    }
+
     public override void OnInspectorGUI()
     {
         // Draws the default convert to UdonBehaviour button, program asset field, sync settings, etc.
@@ -733,13 +808,13 @@ public class WeeklyCalendarEditor : Editor
         {
             Undo.RecordObject(target, "Changed source");
             behavior.Source = new VRCUrl(newSource);
-
-            var source = new Uri(behavior.Source.Get());
-            behavior.Images = new VRCUrl[256];
-            for (var i = 0; i < 256; i++)
-            {
-                behavior.Images[i] = new VRCUrl(new Uri(source, $"posters/{i:x2}").ToString());
-            }
+            UpdateImages(behavior);
+        }
+        else if (!string.IsNullOrEmpty(newSource) && (behavior.Images == null || behavior.Images.Length != MaxPosters || behavior.AtlasImages == null || behavior.AtlasImages.Length != MaxAtlasPages))
+        {
+            // Worlds set up with an older version may only have URLs for the first 256 posters, or none for the atlas.
+            Undo.RecordObject(target, "Updated poster URLs");
+            UpdateImages(behavior);
         }
 
         behavior.ConfigurationShown = EditorGUILayout.BeginFoldoutHeaderGroup(behavior.ConfigurationShown, "Configuration");
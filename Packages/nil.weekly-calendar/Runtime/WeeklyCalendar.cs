@@ -135,7 +135,70 @@ public partial class WeeklyCalendar
             return false;
         }
         var start = TimeSpan.FromMinutes(startt.Double);
-        
+        var dateStr = date.ToString("yyyy-MM-dd");
+
+        if (evt.TryGetValue("reschedule", TokenType.DataList, out var reschedulet))
+        {
+            var reschedule = reschedulet.DataList;
+            for (var i = 0; i < reschedule.Count; i++)
+            {
+                if (!reschedule.TryGetValue(i, TokenType.DataDictionary, out var movedt) ||
+                    !movedt.DataDictionary.TryGetValue("date", TokenType.String, out var fromt))
+                {
+                    LogError("Rescheduled occurrence has no date");
+                    continue;
+                }
+                var moved = movedt.DataDictionary;
+                var to = fromt.String;
+                if (moved.TryGetValue("to", TokenType.String, out var tot))
+                {
+                    to = tot.String;
+                }
+                if (to == dateStr)
+                {
+                    if (!moved.TryGetValue("start", TokenType.Double, out var movedStartt))
+                    {
+                        LogError("Rescheduled occurrence has no start time");
+                        return false;
+                    }
+                    scheduled = moved.ShallowClone();
+                    scheduled["override"] = true;
+                    return ConvertZoneTimeToReal(date + TimeSpan.FromMinutes(movedStartt.Double), zoneName, out inZone);
+                }
+                if (fromt.String == dateStr)
+                {
+                    // The occurrence was moved to a different date.
+                    return true;
+                }
+            }
+        }
+
+        if (evt.TryGetValue("dates", TokenType.DataList, out var datest))
+        {
+            // Special events are only held on the listed dates.
+            var dates = datest.DataList;
+            for (var i = 0; i < dates.Count; i++)
+            {
+                if (dates.TryGetValue(i, TokenType.DataDictionary, out var specialt) &&
+                    specialt.DataDictionary.TryGetValue("date", TokenType.String, out var specialDatet) &&
+                    specialDatet.String == dateStr)
+                {
+                    scheduled = specialt.DataDictionary.ShallowClone();
+                    scheduled["override"] = true;
+                    if (scheduled.TryGetValue("start", TokenType.Double, out var specialStartt))
+                    {
+                        start = TimeSpan.FromMinutes(specialStartt.Double);
+                    }
+                    break;
+                }
+            }
+            if (scheduled == null)
+            {
+                return true;
+            }
+            return ConvertZoneTimeToReal(date + start, zoneName, out inZone);
+        }
+
         if (GetByDay(evt, date.DayOfWeek, TokenType.DataDictionary, out var dayt))
         {
             var day = dayt.DataDictionary;
@@ -151,7 +214,6 @@ public partial class WeeklyCalendar
             return false;
         }
 
-        var dateStr = date.ToString("yyyy-MM-dd");
         if (evt.TryGetValue("confirmed", TokenType.DataList, out var confirmedList))
         {
             if (confirmedList.DataList.Contains(dateStr))
@@ -181,9 +243,10 @@ public partial class WeeklyCalendar
             return true;
         }
 
-        if ((evt.TryGetValue("startDate", TokenType.Double, out var startDatet) && inZone.ToUnixTimeSeconds() < startDatet.Double) ||
-            (evt.TryGetValue("endDate", TokenType.Double, out var endDatet) && endDatet.Double < inZone.ToUnixTimeSeconds()) ||
-            (evt.TryGetValue("weeks", TokenType.DataList, out var weekst) && !weekst.DataList.Contains((double)((date.Day - 1) / 7 + 1))))
+        if ((evt.TryGetValue("start_date", TokenType.Double, out var startDatet) && inZone.ToUnixTimeSeconds() < startDatet.Double) ||
+            (evt.TryGetValue("end_date", TokenType.Double, out var endDatet) && endDatet.Double < inZone.ToUnixTimeSeconds()) ||
+            (evt.TryGetValue("weeks", TokenType.DataList, out var weekst) && !IsScheduledWeekOfMonth(weekst.DataList, date)) ||
+            !IsScheduledWeek(evt, date, zoneName))
         {
             EnsureOwned(ref scheduled, ref scheduledIsOwned);
             scheduled["hide"] = true;
@@ -200,6 +263,31 @@ public partial class WeeklyCalendar
         return true;
     }
 
+    bool IsScheduledWeekOfMonth(DataList weeks, DateTime date)
+    {
+        // Negative weeks are counted from the end of the month.
+        var week = (date.Day - 1) / 7 + 1;
+        var weekFromEnd = -((DateTime.DaysInMonth(date.Year, date.Month) - date.Day) / 7 + 1);
+        return weeks.Contains((double)week) || weeks.Contains((double)weekFromEnd);
+    }
+
+    bool IsScheduledWeek(DataDictionary evt, DateTime date, string zoneName)
+    {
+        if (!evt.TryGetValue("every", TokenType.Double, out var everyt) ||
+            !evt.TryGetValue("start_date", TokenType.Double, out var startDatet))
+        {
+            return true;
+        }
+        if (!ConvertRealTimeToZone(DateTimeOffset.FromUnixTimeSeconds((long)startDatet.Double), zoneName, out var startDate))
+        {
+            return true;
+        }
+        // Weeks are counted from the start date, not from the start of the calendar week.
+        var days = (date - startDate.Date).Days;
+        var weeks = days >= 0 ? days / 7 : (days - 6) / 7;
+        return weeks % (int)everyt.Double == 0;
+    }
+
     void EnsureOwned(ref DataDictionary value, ref bool isOwned)
     {
         if (isOwned)
@@ -331,6 +419,13 @@ public partial class WeeklyCalendar
                 CopyToken(occurrence, "join", evt, language, dayOfWeek);
                 CopyToken(occurrence, "world", evt, language, dayOfWeek);
                 CopyToken(occurrence, "platforms", evt);
+                CopyToken(occurrence, "tags", evt);
+                if (scheduled.TryGetValue("override", TokenType.Boolean, out token) && token.Boolean)
+                {
+                    // Special dates and rescheduled occurrences have their own details.
+                    CopyToken(occurrence, "duration", scheduled);
+                    CopyToken(occurrence, "desc", scheduled);
+                }
 
                 day.Add(occurrence);
             }
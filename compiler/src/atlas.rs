@@ -0,0 +1,204 @@
+//! Packing posters into a few large images, so worlds can load the posters for the whole week with
+//! a few requests instead of one for each poster.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Cursor, Write},
+    path::Path,
+};
+
+use image::{imageops, DynamicImage, ImageOutputFormat, RgbaImage};
+use miette::{Context, IntoDiagnostic};
+
+use crate::{input, output::AtlasRect, safely_save};
+
+/// The largest width and height of a page. VRChat can't load larger images.
+const SIZE: u32 = input::MAX_POSTER_SIZE;
+
+/// The space between posters, so they don't bleed into each other when the page is mipmapped.
+const PADDING: u32 = 8;
+
+/// The most pages the output can have. Worlds have a URL for each page, so the calendar script's
+/// `AtlasImages` must have this many URLs.
+const MAX_PAGES: usize = 16;
+
+struct Row {
+    page: usize,
+    /// Where the next poster in the row goes.
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+struct Placement {
+    page: usize,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+pub struct Atlas {
+    /// The size of each page, which is only as large as the posters on it need.
+    pages: Vec<(u32, u32)>,
+    placements: BTreeMap<u16, Placement>,
+}
+
+impl Atlas {
+    /// Packs posters, given by their number and size, into rows from the tallest to the shortest.
+    /// Each poster goes in the first row with enough space, or starts a new row.
+    ///
+    /// Posters that don't fit in any page are left out, and the calendar script loads them on their
+    /// own.
+    pub fn pack(posters: &BTreeMap<u16, (u16, u16)>) -> Self {
+        let mut sorted: Vec<_> = posters
+            .iter()
+            .map(|(&number, &(width, height))| (number, u32::from(width), u32::from(height)))
+            .collect();
+        sorted.sort_by_key(|&(number, _, height)| (Reverse(height), number));
+
+        let mut pages: Vec<(u32, u32)> = Vec::new();
+        let mut rows: Vec<Row> = Vec::new();
+        let mut placements = BTreeMap::new();
+        for (number, width, height) in sorted {
+            let row = match rows
+                .iter()
+                .position(|r| height <= r.height && r.x + width <= SIZE)
+            {
+                Some(row) => row,
+                None => {
+                    let page = pages
+                        .iter()
+                        .position(|&(_, used)| used + PADDING + height <= SIZE)
+                        .or_else(|| {
+                            (pages.len() < MAX_PAGES).then(|| {
+                                pages.push((0, 0));
+                                pages.len() - 1
+                            })
+                        });
+                    let Some(page) = page else {
+                        continue;
+                    };
+                    let y = match pages[page].1 {
+                        0 => 0,
+                        used => used + PADDING,
+                    };
+                    rows.push(Row {
+                        page,
+                        x: 0,
+                        y,
+                        height,
+                    });
+                    pages[page].1 = y + height;
+                    rows.len() - 1
+                }
+            };
+
+            let row = &mut rows[row];
+            let size = &mut pages[row.page];
+            size.0 = size.0.max(row.x + width);
+            placements.insert(
+                number,
+                Placement {
+                    page: row.page,
+                    x: row.x,
+                    y: row.y,
+                    width,
+                    height,
+                },
+            );
+            row.x += width + PADDING;
+        }
+
+        Atlas { pages, placements }
+    }
+
+    /// Gets where a poster is in the atlas, if it fit.
+    pub fn rect(&self, number: u16) -> Option<AtlasRect> {
+        let placement = self.placements.get(&number)?;
+        let (page_width, page_height) = self.pages[placement.page];
+        let (page_width, page_height) = (page_width as f32, page_height as f32);
+        Some(AtlasRect {
+            page: placement.page as u8,
+            uv: [
+                placement.x as f32 / page_width,
+                1.0 - (placement.y + placement.height) as f32 / page_height,
+                placement.width as f32 / page_width,
+                placement.height as f32 / page_height,
+            ],
+        })
+    }
+
+    /// Draws the pages from the posters that were saved in the output and saves them in the
+    /// `atlas` directory. Pages left over from earlier builds are removed.
+    pub fn save(&self, output: &Path, options: &input::PosterOptions) -> miette::Result<()> {
+        let directory = output.join("atlas");
+        if !directory.exists() {
+            fs::create_dir(&directory)
+                .into_diagnostic()
+                .wrap_err("Could not create atlas directory")?;
+        }
+
+        let mut names = BTreeSet::new();
+        for (page, &(width, height)) in self.pages.iter().enumerate() {
+            let mut image = RgbaImage::new(width, height);
+            for (number, placement) in self.placements.iter().filter(|(_, p)| p.page == page) {
+                let path = output.join("posters").join(format!("{number:02x}"));
+                let poster = image::io::Reader::open(&path)
+                    .and_then(|r| r.with_guessed_format())
+                    .into_diagnostic()
+                    .and_then(|r| r.decode().into_diagnostic())
+                    .wrap_err_with(|| format!("Could not read poster {}", path.display()))?;
+                imageops::replace(
+                    &mut image,
+                    &poster.to_rgba8(),
+                    placement.x.into(),
+                    placement.y.into(),
+                );
+            }
+
+            let mut encoded = Cursor::new(Vec::new());
+            match options.format {
+                Some(input::PosterFormat::Jpeg) => DynamicImage::ImageRgba8(image)
+                    .to_rgb8()
+                    .write_to(&mut encoded, ImageOutputFormat::Jpeg(options.quality.0)),
+                _ => image.write_to(&mut encoded, ImageOutputFormat::Png),
+            }
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Could not encode atlas page {page}"))?;
+
+            let name = format!("{page:02x}");
+            safely_save(&directory, &name, |t| {
+                t.write_all(&encoded.into_inner()).into_diagnostic()
+            })?;
+            names.insert(name);
+        }
+
+        for file in fs::read_dir(&directory)
+            .into_diagnostic()
+            .wrap_err("Reading the atlas directory failed.")?
+        {
+            let file = file
+                .into_diagnostic()
+                .wrap_err("Reading the atlas directory failed.")?;
+            if !file.file_name().to_str().is_some_and(|n| names.contains(n)) {
+                fs::remove_file(file.path())
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Deleting {} failed.", file.path().display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the atlas if it was saved by an earlier build, so it doesn't go stale.
+pub fn remove(output: &Path) -> miette::Result<()> {
+    match fs::remove_dir_all(output.join("atlas")) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e)
+            .into_diagnostic()
+            .wrap_err("Deleting the atlas directory failed."),
+        _ => Ok(()),
+    }
+}
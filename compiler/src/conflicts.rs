@@ -0,0 +1,201 @@
+//! Warnings for events that need the same world, group or organizer at the same time.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    iter,
+    ops::Range,
+    str::FromStr,
+};
+
+use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
+use chrono_tz::Tz;
+use miette::SourceSpan;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    diagnostics,
+    error::{ConflictingEvent, ScheduleConflict},
+    import::day_name,
+    input, occurrences, output, Event, EventFile,
+};
+
+/// How far ahead occurrences are compared.
+const WEEKS: i64 = 4;
+
+/// Something that can only be used by one event at a time, identified by its id.
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+enum Resource<'a> {
+    World(&'a str),
+    Group(&'a str),
+    User(&'a str),
+}
+
+struct Booking<'a> {
+    event: usize,
+    resource: Resource<'a>,
+    /// The name the event gives the resource, which might not match other events.
+    name: &'a str,
+    /// The day of the week the occurrence was scheduled for, which chooses the day details.
+    weekday: Weekday,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// Reports each pair of events that use the same world, group or join user at overlapping times
+/// in the next few weeks. Canceled occurrences are ignored.
+pub fn check(events: &[(&Event, &output::Event)], now: DateTime<Utc>) {
+    let until = now + Duration::weeks(WEEKS);
+    let mut bookings = Vec::new();
+    for (index, &(event, output)) in events.iter().enumerate() {
+        let Ok(tz) = Tz::from_str(output.timezone) else {
+            continue;
+        };
+        for occurrence in occurrences::expand(event, tz, now, until) {
+            if occurrence.status == occurrences::Status::Canceled {
+                continue;
+            }
+            let day = event
+                .event
+                .days
+                .as_ref()
+                .and_then(|days| days.get(occurrence.date.weekday()))
+                .map(|day| &day.info);
+            for (resource, name) in resources(day, &event.event.info) {
+                bookings.push(Booking {
+                    event: index,
+                    resource,
+                    name,
+                    weekday: occurrence.date.weekday(),
+                    start: occurrence.start,
+                    end: occurrence.end,
+                });
+            }
+        }
+    }
+
+    bookings.sort_by_key(|b| b.start);
+    let mut reported = BTreeSet::new();
+    for (i, booking) in bookings.iter().enumerate() {
+        for other in bookings[i + 1..]
+            .iter()
+            .take_while(|o| o.start < booking.end)
+        {
+            if other.event == booking.event
+                || other.resource != booking.resource
+                || !reported.insert((
+                    booking.event.min(other.event),
+                    booking.event.max(other.event),
+                    booking.resource,
+                ))
+            {
+                continue;
+            }
+            let (first, first_output) = events[booking.event];
+            let (second, second_output) = events[other.event];
+            let (first_file, first_location) = find(first, booking.weekday, booking.resource);
+            let (second_file, second_location) = find(second, other.weekday, other.resource);
+            diagnostics::report(ScheduleConflict {
+                event: first_output.name.to_string(),
+                other: second_output.name.to_string(),
+                resource: describe(booking.resource, booking.name),
+                time: booking.start.max(other.start),
+                src: first_file.into(),
+                location: first_location,
+                related: vec![ConflictingEvent {
+                    event: second_output.name.to_string(),
+                    src: second_file.into(),
+                    location: second_location,
+                }],
+            });
+        }
+    }
+}
+
+/// Gets the resources an occurrence uses and their names. Details for the day replace the event's
+/// details.
+fn resources<'a>(
+    day: Option<&'a input::EventInfo<'a>>,
+    event: &'a input::EventInfo<'a>,
+) -> Vec<(Resource<'a>, &'a str)> {
+    let mut resources = Vec::new();
+    if let Some(world) = day.and_then(|d| d.world.as_ref()).or(event.world.as_ref()) {
+        resources.push((Resource::World(&world.id), &*world.name));
+    }
+    if let Some(group) = day
+        .and_then(|d| d.group.as_deref())
+        .or(event.group.as_deref())
+    {
+        resources.push((Resource::Group(group), group));
+    }
+    let join = match day {
+        Some(day) if !day.join.is_empty() => &day.join,
+        _ => &event.join,
+    };
+    resources.extend(
+        join.iter()
+            .map(|user| (Resource::User(&user.id), &*user.name)),
+    );
+    resources
+}
+
+fn describe(resource: Resource, name: &str) -> String {
+    match resource {
+        Resource::World(_) => format!("the world {name:?}"),
+        Resource::Group(id) => format!("the group {id}"),
+        Resource::User(_) => format!("the join user {name:?}"),
+    }
+}
+
+/// Where the resources are set in an event or base file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Locations {
+    world: Option<Id>,
+    group: Option<Spanned<String>>,
+    join: Vec<Id>,
+    days: HashMap<String, Locations>,
+}
+
+#[derive(Deserialize)]
+struct Id {
+    id: Spanned<String>,
+}
+
+impl Locations {
+    fn get(&self, resource: Resource) -> Option<Range<usize>> {
+        let id = match resource {
+            Resource::World(id) => self
+                .world
+                .as_ref()
+                .map(|w| &w.id)
+                .filter(|w| w.get_ref() == id),
+            Resource::Group(id) => self.group.as_ref().filter(|g| g.get_ref() == id),
+            Resource::User(id) => self.join.iter().map(|u| &u.id).find(|u| u.get_ref() == id),
+        };
+        id.map(|id| id.span())
+    }
+}
+
+/// Finds where a resource is set for an occurrence on `weekday`. Inherited resources are found in
+/// the base file.
+fn find<'a>(
+    event: &Event<'a>,
+    weekday: Weekday,
+    resource: Resource,
+) -> (&'a EventFile<'a>, Option<SourceSpan>) {
+    for file in iter::once(event.source).chain(event.base) {
+        let Ok(locations) = Locations::deserialize(toml::Deserializer::new(&file.content)) else {
+            continue;
+        };
+        let span = locations
+            .days
+            .get(day_name(weekday))
+            .and_then(|day| day.get(resource))
+            .or_else(|| locations.get(resource));
+        if let Some(span) = span {
+            return (file, Some(span.into()));
+        }
+    }
+    (event.source, None)
+}
@@ -0,0 +1,536 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use clap::ValueEnum;
+use miette::{Diagnostic, LabeledSpan, Report, Severity, SourceCode, SourceSpan};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ImageTooLarge, MultiplePosters};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Format {
+    /// Human readable output on stderr.
+    #[default]
+    Fancy,
+    /// A JSON array of diagnostics on stdout.
+    Json,
+    /// A SARIF 2.1.0 log on stdout.
+    Sarif,
+    /// GitHub Actions workflow commands on stdout.
+    Github,
+}
+
+/// What to do with a warning.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Every diagnostic code, so lints with typos can be reported.
+const CODES: &[&str] = &[
+    "wc::parse",
+    "wc::state::parse",
+    "wc::lint::unknown",
+    "wc::time_zone::unknown",
+    "wc::end_date::before_start",
+    "wc::every::no_start_date",
+    "wc::dates::conflict",
+    "wc::dates::empty",
+    "wc::weeks::empty",
+    "wc::week::invalid",
+    "wc::week::duplicate",
+    "wc::date::duplicate",
+    "wc::duration::overlap",
+    "wc::schedule::conflict",
+    "wc::extends::missing",
+    "wc::extends::overridden",
+    "wc::name::duplicate",
+    "wc::tag::unknown",
+    "wc::poster::too_large",
+    "wc::poster::multiple",
+    "wc::confirmed::out_of_range",
+    "wc::canceled::out_of_range",
+    "wc::canceled::confirmed",
+    "wc::reschedule::out_of_range",
+    "wc::reschedule::overlap",
+    "wc::import::invalid",
+    "wc::import::unsupported",
+    "wc::import::ignored",
+    "wc::import::exists",
+];
+
+/// Checks whether a lint code is `warnings`, a diagnostic code or a prefix of one.
+pub fn is_known(code: &str) -> bool {
+    code == "warnings"
+        || CODES.iter().any(|known| {
+            known
+                .strip_prefix(code)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+}
+
+/// Lint levels by diagnostic code.
+///
+/// A level set for a code also applies to the codes under it, so `wc::poster` covers
+/// `wc::poster::multiple`. The special code `warnings` covers every warning.
+#[derive(Default)]
+pub struct Lints(HashMap<String, Level>);
+
+impl Lints {
+    pub fn set(&mut self, code: impl Into<String>, level: Level) {
+        self.0.insert(code.into(), level);
+    }
+
+    fn get(&self, mut code: &str) -> Option<Level> {
+        loop {
+            if let Some(&level) = self.0.get(code) {
+                return Some(level);
+            }
+            match code.rsplit_once("::") {
+                Some((parent, _)) => code = parent,
+                None => return self.0.get("warnings").copied(),
+            }
+        }
+    }
+}
+
+struct Diagnostics {
+    format: Format,
+    /// Lints from the command line take precedence over lints from meta.toml.
+    command_line_lints: Lints,
+    meta_lints: Mutex<Lints>,
+    errors: AtomicUsize,
+    records: Mutex<Vec<Record>>,
+}
+
+impl Diagnostics {
+    fn new(format: Format, command_line_lints: Lints) -> Self {
+        Diagnostics {
+            format,
+            command_line_lints,
+            meta_lints: Default::default(),
+            errors: AtomicUsize::new(0),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn level(&self, code: &str) -> Option<Level> {
+        self.command_line_lints
+            .get(code)
+            .or_else(|| self.meta_lints.lock().unwrap().get(code))
+    }
+}
+
+static DIAGNOSTICS: OnceLock<Diagnostics> = OnceLock::new();
+
+fn diagnostics() -> &'static Diagnostics {
+    DIAGNOSTICS.get_or_init(|| Diagnostics::new(Format::default(), Lints::default()))
+}
+
+pub fn init(format: Format, lints: Lints) {
+    let initialized = DIAGNOSTICS.set(Diagnostics::new(format, lints));
+    assert!(initialized.is_ok(), "diagnostics were already initialized");
+}
+
+/// Sets the lint levels from meta.toml.
+pub fn set_meta_lints(lints: Lints) {
+    *diagnostics().meta_lints.lock().unwrap() = lints;
+}
+
+/// The number of errors reported so far.
+pub fn errors() -> usize {
+    diagnostics().errors.load(Ordering::SeqCst)
+}
+
+/// Reports a problem in the configured format.
+///
+/// Warnings are adjusted according to the lint levels. Errors are always reported as errors.
+pub fn report(report: impl Into<Report>) {
+    let mut report = report.into();
+    let diagnostics = diagnostics();
+    let mut severity = report.severity().unwrap_or(Severity::Error);
+    if severity != Severity::Error {
+        let level = report
+            .code()
+            .and_then(|code| diagnostics.level(&code.to_string()));
+        match level {
+            Some(Level::Allow) => return,
+            Some(Level::Warn) | None => {}
+            Some(Level::Deny) => {
+                report = Report::new(Denied(report));
+                severity = Severity::Error;
+            }
+        }
+    }
+    if severity == Severity::Error {
+        diagnostics.errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    match diagnostics.format {
+        Format::Fancy => eprintln!("{report:?}"),
+        Format::Json | Format::Sarif => {
+            let record = Record::new(&report, severity);
+            diagnostics.records.lock().unwrap().push(record);
+        }
+        Format::Github => {
+            eprintln!("{report:?}");
+            println!("{}", Record::new(&report, severity).to_github());
+        }
+    }
+}
+
+/// A warning that was promoted to an error by the lint levels.
+struct Denied(Report);
+
+impl fmt::Debug for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for Denied {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl Diagnostic for Denied {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.0.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Error)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.0.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.0.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.0.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.0.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.0.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.0.diagnostic_source()
+    }
+}
+
+/// Writes out the diagnostics collected for formats that are not streamed.
+pub fn finish() {
+    let diagnostics = diagnostics();
+    let records = std::mem::take(&mut *diagnostics.records.lock().unwrap());
+    let result = match diagnostics.format {
+        Format::Fancy | Format::Github => return,
+        Format::Json => serde_json::to_writer_pretty(io::stdout().lock(), &records),
+        Format::Sarif => serde_json::to_writer_pretty(io::stdout().lock(), &Sarif::new(&records)),
+    };
+    if let Err(error) = result {
+        eprintln!("{error:?}");
+    }
+    println!();
+    _ = io::stdout().flush();
+}
+
+#[derive(Serialize)]
+struct Record {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    severity: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    context: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    help: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Span>,
+}
+
+/// A location in a file. Lines and columns start at 1 and the end column is exclusive.
+#[derive(Serialize)]
+struct Span {
+    offset: usize,
+    length: usize,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl Record {
+    fn new(report: &Report, severity: Severity) -> Self {
+        let mut context: Vec<_> = report.chain().map(|e| e.to_string()).collect();
+        let message = context.pop().unwrap_or_default();
+
+        let diagnostic: &dyn Diagnostic = &**report;
+        let label = diagnostic
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| *label.inner());
+        let (file, span) = match (diagnostic.source_code(), label) {
+            (Some(source), Some(label)) => match source.read_span(&label, 0, 0) {
+                Ok(contents) => (
+                    contents.name().map(str::to_owned),
+                    Some(Span::new(
+                        label,
+                        contents.line(),
+                        contents.column(),
+                        contents.data(),
+                    )),
+                ),
+                Err(_) => (None, None),
+            },
+            _ => (None, None),
+        };
+        // Poster problems are about the image files, which have no source code to point into.
+        let file = file.or_else(|| {
+            let report = report.downcast_ref::<Denied>().map_or(report, |d| &d.0);
+            report
+                .downcast_ref::<ImageTooLarge>()
+                .map(|e| &e.path)
+                .or_else(|| report.downcast_ref::<MultiplePosters>().map(|e| &e.extra))
+                .map(|path| path.to_string_lossy().into_owned())
+        });
+
+        Record {
+            code: diagnostic.code().map(|c| c.to_string()),
+            severity: match severity {
+                Severity::Advice => "advice",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            },
+            message,
+            context,
+            help: diagnostic.help().map(|h| h.to_string()),
+            file,
+            span,
+        }
+    }
+
+    fn to_github(&self) -> String {
+        let command = match self.severity {
+            "error" => "error",
+            "warning" => "warning",
+            _ => "notice",
+        };
+        let mut properties = Vec::new();
+        if let Some(file) = &self.file {
+            properties.push(format!("file={}", escape_github_property(file)));
+        }
+        if let Some(span) = &self.span {
+            properties.push(format!("line={}", span.start_line));
+            properties.push(format!("col={}", span.start_column));
+            properties.push(format!("endLine={}", span.end_line));
+            properties.push(format!("endColumn={}", span.end_column));
+        }
+        if let Some(code) = &self.code {
+            properties.push(format!("title={}", escape_github_property(code)));
+        }
+
+        format!(
+            "::{command} {}::{}",
+            properties.join(","),
+            escape_github_data(&self.text()),
+        )
+    }
+
+    /// The message with its context and help.
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for context in &self.context {
+            text.push_str(context);
+            text.push('\n');
+        }
+        text.push_str(&self.message);
+        if let Some(help) = &self.help {
+            text.push('\n');
+            text.push_str(help);
+        }
+        text
+    }
+}
+
+impl Span {
+    fn new(span: SourceSpan, line: usize, column: usize, data: &[u8]) -> Self {
+        let data = &data[..span.len().min(data.len())];
+        let (end_line, end_column) = match data.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => (
+                line + data.iter().filter(|&&b| b == b'\n').count(),
+                data.len() - newline,
+            ),
+            None => (line, column + data.len() + 1),
+        };
+        Span {
+            offset: span.offset(),
+            length: span.len(),
+            start_line: line + 1,
+            start_column: column + 1,
+            end_line: end_line + 1,
+            end_column,
+        }
+    }
+}
+
+fn escape_github_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[derive(Serialize)]
+struct Sarif<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [SarifRun<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct SarifRun<'a> {
+    tool: SarifTool,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<&'a str>,
+    level: &'static str,
+    message: SarifMessage,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<SarifRegion>,
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl<'a> Sarif<'a> {
+    fn new(records: &'a [Record]) -> Self {
+        Sarif {
+            schema: "https://json.schemastore.org/sarif-2.1.0.json",
+            version: "2.1.0",
+            runs: [SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: "https://github.com/nil-vr/WeeklyCalendar",
+                    },
+                },
+                results: records
+                    .iter()
+                    .map(|record| SarifResult {
+                        rule_id: record.code.as_deref(),
+                        level: match record.severity {
+                            "error" => "error",
+                            "warning" => "warning",
+                            _ => "note",
+                        },
+                        message: SarifMessage {
+                            text: record.text(),
+                        },
+                        locations: record
+                            .file
+                            .iter()
+                            .map(|file| SarifLocation {
+                                physical_location: SarifPhysicalLocation {
+                                    artifact_location: SarifArtifactLocation {
+                                        uri: file.replace('\\', "/"),
+                                    },
+                                    region: record.span.as_ref().map(|span| SarifRegion {
+                                        start_line: span.start_line,
+                                        start_column: span.start_column,
+                                        end_line: span.end_line,
+                                        end_column: span.end_column,
+                                    }),
+                                },
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            }],
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use std::{fmt, path::PathBuf};
 
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc, Weekday};
 use miette::{Diagnostic, NamedSource, SourceOffset, SourceSpan};
 
 use crate::{Event, EventFile};
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
+#[diagnostic(code(wc::parse))]
 pub struct EventParseError {
     pub error: toml::de::Error,
     #[source_code]
@@ -37,6 +38,7 @@ impl EventParseError {
 }
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
+#[diagnostic(code(wc::state::parse))]
 pub struct StateParseError {
     pub error: serde_json::Error,
     #[source_code]
@@ -70,6 +72,7 @@ impl StateParseError {
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
 #[error("Unknown time zone {name:?}")]
+#[diagnostic(code(wc::time_zone::unknown))]
 pub struct MissingTimeZone {
     name: String,
     #[source_code]
@@ -88,9 +91,282 @@ impl MissingTimeZone {
     }
 }
 
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Events that repeat every few weeks must have a start date")]
+#[diagnostic(
+    code(wc::every::no_start_date),
+    help("The weeks are counted from the start date")
+)]
+pub struct EveryWithoutStartDate {
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The event ends before it starts")]
+#[diagnostic(code(wc::end_date::before_start))]
+pub struct EndBeforeStart {
+    #[source_code]
+    pub src: NamedSource,
+    #[label("starts here")]
+    pub start: SourceSpan,
+    #[label("ends here")]
+    pub end: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Special events with dates cannot have {field}")]
+#[diagnostic(
+    code(wc::dates::conflict),
+    help("Special events are only held on the listed dates")
+)]
+pub struct DatesConflict {
+    pub field: &'static str,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Special events must have at least one date")]
+#[diagnostic(code(wc::dates::empty))]
+pub struct NoDates {
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Week {week} does not exist")]
+#[diagnostic(
+    code(wc::week::invalid),
+    help("Weeks are 1 to 5, or -1 to -5 to count from the end of the month")
+)]
+pub struct InvalidWeek {
+    pub week: i8,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Events with weeks must have at least one week")]
+#[diagnostic(code(wc::weeks::empty))]
+pub struct NoWeeks {
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The event is rescheduled from {date}, but the event is not happening on this day.")]
+#[diagnostic(code(wc::reschedule::out_of_range), severity("warning"))]
+pub struct RescheduleOutOfRange {
+    pub date: NaiveDate,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The occurrence moved from {date} overlaps the occurrence on {other}")]
+#[diagnostic(code(wc::reschedule::overlap), severity("warning"))]
+pub struct RescheduleOverlap {
+    pub date: NaiveDate,
+    pub other: NaiveDate,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{event} and {other} both use {resource} at {time}")]
+#[diagnostic(code(wc::schedule::conflict), severity("warning"))]
+pub struct ScheduleConflict {
+    pub event: String,
+    pub other: String,
+    pub resource: String,
+    pub time: DateTime<Utc>,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: Option<SourceSpan>,
+    #[related]
+    pub related: Vec<ConflictingEvent>,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{event} is scheduled at the same time")]
+#[diagnostic(severity("warning"))]
+pub struct ConflictingEvent {
+    pub event: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: Option<SourceSpan>,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The event on {weekday} is still running when the next occurrence starts")]
+#[diagnostic(code(wc::duration::overlap), severity("warning"))]
+pub struct DurationOverlap {
+    pub weekday: Weekday,
+    #[source_code]
+    pub src: NamedSource,
+    #[label("the event lasts this long")]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{date} is listed more than once")]
+#[diagnostic(code(wc::date::duplicate), severity("warning"))]
+pub struct DuplicateDate {
+    pub date: NaiveDate,
+    #[source_code]
+    pub src: NamedSource,
+    #[label("listed again here")]
+    pub location: SourceSpan,
+    #[label("first listed here")]
+    pub first: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The event is both confirmed and canceled for {date}")]
+#[diagnostic(
+    code(wc::canceled::confirmed),
+    severity("warning"),
+    help("The event will be shown as confirmed")
+)]
+pub struct ConfirmedAndCanceled {
+    pub date: NaiveDate,
+    #[source_code]
+    pub src: NamedSource,
+    #[label("confirmed here")]
+    pub confirmed: SourceSpan,
+    #[label("canceled here")]
+    pub canceled: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Week {week} is listed more than once")]
+#[diagnostic(code(wc::week::duplicate), severity("warning"))]
+pub struct DuplicateWeek {
+    pub week: i8,
+    #[source_code]
+    pub src: NamedSource,
+    #[label("listed again here")]
+    pub location: SourceSpan,
+    #[label("first listed here")]
+    pub first: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Base file {path:?} could not be read")]
+#[diagnostic(code(wc::extends::missing))]
+pub struct MissingBase {
+    pub path: PathBuf,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{field} is also set in {base_path:?}")]
+#[diagnostic(
+    code(wc::extends::overridden),
+    severity("warning"),
+    help("The value from this file is used")
+)]
+pub struct ExtendsOverride {
+    pub field: String,
+    pub base_path: PathBuf,
+    #[source_code]
+    pub src: NamedSource,
+    #[label("overrides the base file")]
+    pub location: SourceSpan,
+    #[related]
+    pub base: Vec<BaseValue>,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{field} is set in the base file here")]
+#[diagnostic(severity("advice"))]
+pub struct BaseValue {
+    pub field: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{path:?} has the same name as {first:?}")]
+#[diagnostic(
+    code(wc::name::duplicate),
+    severity("warning"),
+    help("Events in different directories should have different file names or set a name")
+)]
+pub struct DuplicateName {
+    pub path: PathBuf,
+    pub first: PathBuf,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Tag {tag:?} is not defined")]
+#[diagnostic(
+    code(wc::tag::unknown),
+    severity("warning"),
+    help("Tags are defined in the [tags] table of meta.toml")
+)]
+pub struct UnknownTag {
+    pub tag: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{code} doesn't match any diagnostic code")]
+#[diagnostic(
+    code(wc::lint::unknown),
+    severity("warning"),
+    help("Codes are listed in the diagnostics section of the README")
+)]
+pub struct UnknownLint {
+    pub code: String,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{code} doesn't match any diagnostic code")]
+#[diagnostic(
+    code(wc::lint::unknown),
+    severity("warning"),
+    help("Codes are listed in the diagnostics section of the README")
+)]
+pub struct UnknownMetaLint {
+    pub code: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
 #[derive(Debug, Diagnostic, thiserror::Error)]
 #[error("Image {path:?} is too large ({width}x{height})")]
-#[help("Images cannot be larger than 2048x2048")]
+#[diagnostic(
+    code(wc::poster::too_large),
+    help("Images larger than 16384x16384 are not scaled down")
+)]
 pub struct ImageTooLarge {
     pub path: PathBuf,
     pub width: usize,
@@ -99,8 +375,11 @@ pub struct ImageTooLarge {
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
 #[error("Ignoring poster {extra:?} and using {found:?} instead")]
-#[help("Events should only have one poster")]
-#[diagnostic(severity("warning"))]
+#[diagnostic(
+    code(wc::poster::multiple),
+    severity("warning"),
+    help("Events should only have one poster")
+)]
 pub struct MultiplePosters {
     pub found: PathBuf,
     pub extra: PathBuf,
@@ -108,7 +387,7 @@ pub struct MultiplePosters {
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
 #[error("The event is confirmed for {date}, but the event is not happening on this day.")]
-#[diagnostic(severity("warning"))]
+#[diagnostic(code(wc::confirmed::out_of_range), severity("warning"))]
 pub struct ConfirmedOutOfRange {
     pub date: NaiveDate,
     #[source_code]
@@ -119,7 +398,7 @@ pub struct ConfirmedOutOfRange {
 
 #[derive(Debug, Diagnostic, thiserror::Error)]
 #[error("The event is canceled for {date}, but the event is not happening on this day.")]
-#[diagnostic(severity("warning"))]
+#[diagnostic(code(wc::canceled::out_of_range), severity("warning"))]
 pub struct CanceledOutOfRange {
     pub date: NaiveDate,
     #[source_code]
@@ -127,3 +406,52 @@ pub struct CanceledOutOfRange {
     #[label]
     pub location: SourceSpan,
 }
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("The recurrence rule can't be converted: {reason}")]
+#[diagnostic(
+    code(wc::import::unsupported),
+    severity("warning"),
+    help("The event was skipped. It needs to be written by hand.")
+)]
+pub struct UnsupportedRule {
+    pub reason: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{property} can't be converted and was ignored")]
+#[diagnostic(code(wc::import::ignored), severity("warning"))]
+pub struct IgnoredProperty {
+    pub property: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("Invalid {property}: {reason}")]
+#[diagnostic(code(wc::import::invalid))]
+pub struct InvalidProperty {
+    pub property: String,
+    pub reason: String,
+    #[source_code]
+    pub src: NamedSource,
+    #[label]
+    pub location: SourceSpan,
+}
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+#[error("{path:?} already exists")]
+#[diagnostic(
+    code(wc::import::exists),
+    severity("warning"),
+    help("Existing event files are never overwritten. Delete the file to import it again.")
+)]
+pub struct EventFileExists {
+    pub path: PathBuf,
+}
@@ -0,0 +1,98 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::IgnoredAny, Deserialize};
+use toml::Spanned;
+
+use crate::{
+    diagnostics,
+    error::{BaseValue, ExtendsOverride},
+    input, EventFile,
+};
+
+/// Gets the path of a base file relative to the event file that extends it.
+pub fn resolve(file: &EventFile, extends: &str) -> PathBuf {
+    file.path.parent().unwrap_or(Path::new("")).join(extends)
+}
+
+/// Finds the base file an event file extends without parsing the rest of it.
+pub fn base_path(file: &EventFile) -> Option<PathBuf> {
+    #[derive(Deserialize)]
+    struct Extends<'a> {
+        #[serde(borrow)]
+        extends: Option<Cow<'a, str>>,
+    }
+
+    let extends = Extends::deserialize(toml::Deserializer::new(&file.content))
+        .ok()?
+        .extends?;
+    Some(resolve(file, &extends))
+}
+
+/// Copies the details that the event doesn't set itself from the base file. The name is never
+/// inherited because each event needs its own.
+pub fn merge<'a>(info: &mut input::EventInfo<'a>, base: &input::EventInfo<'a>) {
+    fn inherit<T: Clone>(value: &mut Option<T>, base: &Option<T>) {
+        if value.is_none() {
+            value.clone_from(base);
+        }
+    }
+
+    let input::EventInfo {
+        name: _,
+        description,
+        web,
+        poster,
+        hashtag,
+        twitter,
+        group,
+        discord,
+        join,
+        world,
+    } = base;
+    inherit(&mut info.description, description);
+    inherit(&mut info.web, web);
+    inherit(&mut info.poster, poster);
+    inherit(&mut info.hashtag, hashtag);
+    inherit(&mut info.twitter, twitter);
+    inherit(&mut info.group, group);
+    inherit(&mut info.discord, discord);
+    inherit(&mut info.world, world);
+    if info.join.is_empty() {
+        info.join.clone_from(join);
+    }
+}
+
+/// Warns about details that are set in both an event and its base file.
+pub fn check_overrides(file: &EventFile, base: &EventFile) {
+    type Keys = HashMap<String, Spanned<IgnoredAny>>;
+
+    let (Ok(keys), Ok(base_keys)) = (
+        Keys::deserialize(toml::Deserializer::new(&file.content)),
+        Keys::deserialize(toml::Deserializer::new(&base.content)),
+    ) else {
+        return;
+    };
+    let mut overrides: Vec<_> = base_keys
+        .iter()
+        .filter(|(key, _)| key.as_str() != "name")
+        .filter_map(|(key, base_value)| Some((key, keys.get(key)?, base_value)))
+        .collect();
+    overrides.sort_unstable_by_key(|(_, value, _)| value.span().start);
+    for (key, value, base_value) in overrides {
+        diagnostics::report(ExtendsOverride {
+            field: key.clone(),
+            base_path: base.path.to_owned(),
+            src: file.into(),
+            location: value.span().into(),
+            base: vec![BaseValue {
+                field: key.clone(),
+                src: base.into(),
+                location: base_value.span().into(),
+            }],
+        });
+    }
+}
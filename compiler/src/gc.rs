@@ -0,0 +1,129 @@
+//! Removing posters that haven't been used for a while, so the output doesn't keep every poster
+//! forever.
+
+use std::{collections::BTreeSet, fs, path::Path, process::ExitCode};
+
+use chrono::{DateTime, Duration, Utc};
+use miette::{miette, Context, IntoDiagnostic};
+
+use crate::{diagnostics, load_state, save_state, state};
+
+/// Parses an age like `90d` or `12w`.
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let (number, days) = if let Some(number) = value.strip_suffix('d') {
+        (number, 1)
+    } else if let Some(number) = value.strip_suffix('w') {
+        (number, 7)
+    } else {
+        return Err("expected a number of days or weeks, like `90d` or `12w`".to_owned());
+    };
+    let number: u32 = number.parse().map_err(|e| format!("{e}"))?;
+    Ok(Duration::days(i64::from(number) * days))
+}
+
+/// Removes the posters in `output` that haven't been used for longer than `older_than`, along with
+/// their thumbnails and any poster files that aren't in `state.json`.
+pub fn gc(output: &Path, older_than: Duration, dry_run: bool) -> ExitCode {
+    match run(output, older_than, dry_run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            diagnostics::report(e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(output: &Path, older_than: Duration, dry_run: bool) -> miette::Result<()> {
+    // Without the state, every poster file would look unused.
+    if !output.join("state.json").exists() {
+        return Err(miette!("{} has no state.json.", output.display()));
+    }
+    let mut state = load_state(output)?;
+
+    let cutoff = Utc::now()
+        .checked_sub_signed(older_than)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    // The posters from the last build are always kept, because the published data still uses them.
+    let latest = state.posters.iter().map(|p| p.last_used).max();
+    let (kept, removed): (Vec<_>, Vec<_>) = state
+        .posters
+        .drain(..)
+        .partition(|p| p.last_used >= cutoff || Some(p.last_used) == latest);
+    state.posters = kept;
+
+    let verb = if dry_run { "Would remove" } else { "Removing" };
+    for poster in &removed {
+        eprintln!(
+            "{verb} poster {:02x}, last used on {}",
+            poster.number,
+            poster.last_used.format("%Y-%m-%d"),
+        );
+    }
+    // The numbers aren't reused right away, because worlds with old data might still show them.
+    state.retired.extend(removed.iter().map(|p| state::Retired {
+        number: p.number,
+        last_used: p.last_used,
+    }));
+
+    let kept: BTreeSet<_> = state
+        .posters
+        .iter()
+        .map(|p| format!("{:02x}", p.number))
+        .collect();
+    let mut files = Vec::new();
+    let mut size = 0;
+    for directory in ["posters", "thumbnails"] {
+        let directory = output.join(directory);
+        if !directory.exists() {
+            continue;
+        }
+        for file in fs::read_dir(&directory)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Reading {} failed.", directory.display()))?
+        {
+            let file = file
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Reading {} failed.", directory.display()))?;
+            // Files with other names weren't saved by the compiler.
+            let unused = file.file_name().to_str().is_some_and(|name| {
+                u16::from_str_radix(name, 16).is_ok_and(|n| format!("{n:02x}") == name)
+                    && !kept.contains(name)
+            });
+            if unused {
+                size += file
+                    .metadata()
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Reading {} failed.", file.path().display()))?
+                    .len();
+                files.push(file.path());
+            }
+        }
+    }
+
+    if !dry_run {
+        // The state is saved first, so a file that can't be deleted is only left over, instead of
+        // the state listing a poster whose file is gone.
+        save_state(output, &state)?;
+        for file in &files {
+            fs::remove_file(file)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Deleting {} failed.", file.display()))?;
+        }
+    }
+
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let size = if size < 1024 * 1024 {
+        format!("{:.1} KiB", size as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0))
+    };
+    eprintln!(
+        "{} {} poster{} and {} file{} ({size}).",
+        if dry_run { "Would free" } else { "Freed" },
+        removed.len(),
+        plural(removed.len()),
+        files.len(),
+        plural(files.len()),
+    );
+    Ok(())
+}
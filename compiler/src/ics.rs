@@ -0,0 +1,478 @@
+//! iCalendar export, so the calendar can be subscribed to from other calendar apps.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use chrono::{
+    DateTime, Datelike, Days, Duration, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+use miette::{Context, IntoDiagnostic};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::{
+    occurrences,
+    output::{self, DateSet, Details, Zone},
+    safely_save, Event, Language, WEEKDAYS,
+};
+
+/// Characters that are escaped in the names of the files for single events.
+const FILE_NAME: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.');
+
+/// Saves `calendar.ics` with all of the events, `calendar.<lang>.ics` for each language, and a file
+/// for each event in the `ics` directory.
+pub fn save(
+    output: &Path,
+    meta: &output::Meta,
+    events: &[(&Event, &output::Event)],
+    zones: &BTreeMap<String, Zone>,
+    now: DateTime<Utc>,
+) -> miette::Result<()> {
+    safely_save(output, "calendar.ics", |t| {
+        write_calendar(t, meta.title, meta.description, None, events, zones, now).into_diagnostic()
+    })?;
+
+    let languages: BTreeSet<_> = meta
+        .languages
+        .keys()
+        .chain(events.iter().flat_map(|(_, e)| e.languages.keys()))
+        .copied()
+        .collect();
+    let mut calendars = BTreeSet::from(["calendar.ics".to_owned()]);
+    for language in languages {
+        let meta_language = meta.languages.get(&language);
+        let title = meta_language.and_then(|m| m.title).unwrap_or(meta.title);
+        let description = meta_language
+            .and_then(|m| m.description)
+            .or(meta.description);
+        let name = format!("calendar.{}.ics", language.code());
+        safely_save(output, &name, |t| {
+            write_calendar(t, title, description, Some(language), events, zones, now)
+                .into_diagnostic()
+        })?;
+        calendars.insert(name);
+    }
+
+    // Remove the calendars for languages that are no longer used.
+    for entry in fs::read_dir(output)
+        .into_diagnostic()
+        .wrap_err("Reading the output directory failed.")?
+    {
+        let entry = entry
+            .into_diagnostic()
+            .wrap_err("Reading the output directory failed.")?;
+        let name = entry.file_name();
+        if name.to_str().is_some_and(|n| {
+            n.starts_with("calendar.") && n.ends_with(".ics") && !calendars.contains(n)
+        }) {
+            fs::remove_file(entry.path())
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Deleting {} failed.", entry.path().display()))?;
+        }
+    }
+
+    let directory = output.join("ics");
+    if !directory.exists() {
+        fs::create_dir(&directory)
+            .into_diagnostic()
+            .wrap_err("Could not create ics directory")?;
+    }
+    let mut names = BTreeSet::new();
+    for event in events {
+        let name = file_name(event.1);
+        safely_save(&directory, &name, |t| {
+            write_calendar(t, &event.1.name, None, None, &[*event], zones, now).into_diagnostic()
+        })?;
+        names.insert(name);
+    }
+
+    // Remove the files for events that no longer exist.
+    for entry in fs::read_dir(&directory)
+        .into_diagnostic()
+        .wrap_err("Reading the ics directory failed.")?
+    {
+        let entry = entry
+            .into_diagnostic()
+            .wrap_err("Reading the ics directory failed.")?;
+        let name = entry.file_name();
+        if name.to_str().is_some_and(|n| !names.contains(n)) {
+            fs::remove_file(entry.path())
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Deleting {} failed.", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the name of the file for a single event.
+fn file_name(event: &output::Event) -> String {
+    format!("{}.ics", utf8_percent_encode(&event.name, FILE_NAME))
+}
+
+fn write_calendar(
+    w: impl Write,
+    name: &str,
+    description: Option<&str>,
+    language: Option<Language>,
+    events: &[(&Event, &output::Event)],
+    zones: &BTreeMap<String, Zone>,
+    now: DateTime<Utc>,
+) -> io::Result<()> {
+    let mut lines = Lines(w);
+    lines.property("BEGIN", "VCALENDAR")?;
+    lines.property("VERSION", "2.0")?;
+    lines.property("PRODID", "-//nil-vr//wc-compiler//EN")?;
+    lines.property("CALSCALE", "GREGORIAN")?;
+    lines.property("METHOD", "PUBLISH")?;
+    lines.text("X-WR-CALNAME", name)?;
+    if let Some(description) = description {
+        lines.text("X-WR-CALDESC", description)?;
+    }
+
+    let time_zones: BTreeSet<_> = events.iter().map(|(_, e)| e.timezone).collect();
+    for name in time_zones {
+        if let Some(zone) = zones.get(name) {
+            write_time_zone(&mut lines, name, zone)?;
+        }
+    }
+    for &(event, output) in events {
+        write_event(&mut lines, event, output, language, now)?;
+    }
+
+    lines.property("END", "VCALENDAR")
+}
+
+/// Writes the offsets from the compiled time zone data. The first offset is also used for any
+/// occurrences before the compiled time, which only matters for old start dates.
+fn write_time_zone(lines: &mut Lines<impl Write>, name: &str, zone: &Zone) -> io::Result<()> {
+    let offset = |rule: &output::Rule| i32::from(rule.offset.unwrap_or(0));
+    let standard = zone.offsets.iter().map(offset).min().unwrap_or(0);
+
+    lines.property("BEGIN", "VTIMEZONE")?;
+    lines.property("TZID", name)?;
+    let mut previous = zone.offsets.first().map_or(0, offset);
+    for rule in &zone.offsets {
+        let to = offset(rule);
+        let component = if to > standard {
+            "DAYLIGHT"
+        } else {
+            "STANDARD"
+        };
+        let start = match rule.start {
+            Some(start) => DateTime::from_timestamp(start + i64::from(previous) * 60, 0)
+                .unwrap_or_default()
+                .naive_utc(),
+            None => NaiveDate::from_ymd_opt(1970, 1, 1)
+                .unwrap()
+                .and_time(NaiveTime::MIN),
+        };
+        lines.property("BEGIN", component)?;
+        lines.property("DTSTART", &format_local(start))?;
+        lines.property("TZOFFSETFROM", &format_offset(previous))?;
+        lines.property("TZOFFSETTO", &format_offset(to))?;
+        lines.property("END", component)?;
+        previous = to;
+    }
+    lines.property("END", "VTIMEZONE")
+}
+
+/// Days of the week that share a schedule and so can share a recurrence rule.
+struct Group<'a> {
+    weekdays: Vec<Weekday>,
+    start: NaiveTime,
+    duration: Duration,
+    details: Details<'a>,
+}
+
+fn write_event(
+    lines: &mut Lines<impl Write>,
+    event: &Event,
+    output: &output::Event,
+    language: Option<Language>,
+    now: DateTime<Utc>,
+) -> io::Result<()> {
+    let Ok(tz) = Tz::from_str(output.timezone) else {
+        return Ok(());
+    };
+    let uid = utf8_percent_encode(&output.name, FILE_NAME).to_string();
+    let stamp = format_utc(now.naive_utc());
+
+    if let Some(dates) = &output.dates {
+        for date in dates {
+            let (start, duration) = event.get_schedule_for_day(date.date);
+            lines.property("BEGIN", "VEVENT")?;
+            lines.property("UID", &format!("{uid}/{}", date.date.format("%Y%m%d")))?;
+            lines.property("DTSTAMP", &stamp)?;
+            write_times(lines, tz, date.date.and_time(start), duration)?;
+            if occurrences::status(event, date.date) == occurrences::Status::Canceled {
+                lines.property("STATUS", "CANCELLED")?;
+            }
+            write_details(lines, output, Details::new(output, language, None))?;
+            lines.property("END", "VEVENT")?;
+        }
+        return Ok(());
+    }
+
+    let mut groups: Vec<Group> = Vec::new();
+    for weekday in WEEKDAYS {
+        if let Some(days) = &event.event.days {
+            if days.get(weekday).is_none() {
+                continue;
+            }
+        }
+        let (start, duration) = event.get_schedule_for_weekday(weekday);
+        let details = Details::new(output, language, Some(weekday));
+        match groups
+            .iter_mut()
+            .find(|g| (g.start, g.duration, g.details) == (start, duration, details))
+        {
+            Some(group) => group.weekdays.push(weekday),
+            None => groups.push(Group {
+                weekdays: vec![weekday],
+                start,
+                duration,
+                details,
+            }),
+        }
+    }
+
+    let today = now.with_timezone(&tz).date_naive();
+    let every = event.event.every.as_ref().map_or(1, |e| e.as_ref().0);
+    let first_search = event
+        .event
+        .start_date
+        .as_ref()
+        .map(|d| *d.as_ref())
+        .into_iter()
+        .chain([today - Days::new(7)])
+        .max()
+        .unwrap();
+    // Weeks are counted from the start date, so that's when they start for the interval.
+    let week_start = event
+        .event
+        .start_date
+        .as_ref()
+        .map_or(Weekday::Mon, |d| d.as_ref().weekday());
+    for group in &groups {
+        // The first occurrence is the start of the recurrence rule.
+        let occurs = |date: NaiveDate| {
+            group.weekdays.contains(&date.weekday())
+                && matches!(event.get_time_for_day(date, tz), Ok(Some(_)))
+        };
+        let Some(first) = first_search
+            .iter_days()
+            .take(366 * usize::from(every))
+            .find(|&d| occurs(d))
+        else {
+            continue;
+        };
+        let days: Vec<_> = group.weekdays.iter().map(|&d| weekday_code(d)).collect();
+        // UNTIL is in UTC, so it's the end of the last day converted from local time.
+        let until = event.event.end_date.as_ref().map(|d| {
+            let end = d.as_ref().and_time(NaiveTime::MIN) + Days::new(1);
+            let offset = tz.offset_from_utc_datetime(&end).fix().local_minus_utc();
+            format!(
+                ";UNTIL={}",
+                format_utc(end - Duration::seconds(i64::from(offset) + 1))
+            )
+        });
+        let until = until.as_deref().unwrap_or_default();
+        let rule = match (&event.event.weeks, event.event.every.is_some()) {
+            (None, false) => Some(format!("FREQ=WEEKLY;BYDAY={}{until}", days.join(","))),
+            (None, true) => Some(format!(
+                "FREQ=WEEKLY;INTERVAL={every};WKST={};BYDAY={}{until}",
+                weekday_code(week_start),
+                days.join(","),
+            )),
+            (Some(weeks), false) => {
+                let days: Vec<_> = weeks
+                    .as_ref()
+                    .iter()
+                    .flat_map(|w| days.iter().map(move |d| format!("{}{d}", w.as_ref().0)))
+                    .collect();
+                Some(format!("FREQ=MONTHLY;BYDAY={}{until}", days.join(",")))
+            }
+            // There is no rule for combining weeks of the month and repeating every few weeks,
+            // so the dates for the next year are listed instead.
+            (Some(_), true) => None,
+        };
+
+        let uid = format!("{uid}/{}", days.join(""));
+        let start = first.and_time(group.start);
+        lines.property("BEGIN", "VEVENT")?;
+        lines.property("UID", &uid)?;
+        lines.property("DTSTAMP", &stamp)?;
+        write_times(lines, tz, start, group.duration)?;
+        // Events that are canceled entirely stay in the calendar so subscribers see why they're
+        // gone.
+        if output.canceled.is_all() {
+            lines.property("STATUS", "CANCELLED")?;
+        }
+        match rule {
+            Some(rule) => lines.property("RRULE", &rule)?,
+            None => {
+                // Time zones are less than a day from UTC.
+                let from = Utc.from_utc_datetime(&start) - Duration::days(1);
+                let occurrences = occurrences::expand(event, tz, from, from + Duration::days(368));
+                let last = first + Days::new(365);
+                // Moved occurrences are listed on their original dates for RECURRENCE-ID.
+                for date in occurrences
+                    .iter()
+                    .map(|o| o.date)
+                    .filter(|&d| first < d && d <= last && group.weekdays.contains(&d.weekday()))
+                {
+                    lines.property(
+                        &format!("RDATE;TZID={tz}"),
+                        &format_local(date.and_time(group.start)),
+                    )?;
+                }
+            }
+        }
+        if let DateSet::Dates(canceled) = &output.canceled {
+            for &date in canceled.iter().filter(|d| occurs(**d)) {
+                lines.property(
+                    &format!("EXDATE;TZID={tz}"),
+                    &format_local(date.and_time(group.start)),
+                )?;
+            }
+        }
+        write_details(lines, output, group.details)?;
+        lines.property("END", "VEVENT")?;
+
+        for reschedule in output.reschedule.iter().filter(|r| occurs(r.date)) {
+            let to = reschedule.to.unwrap_or(reschedule.date);
+            let start = to.and_time(NaiveTime::MIN) + Duration::minutes(reschedule.start.0.into());
+            lines.property("BEGIN", "VEVENT")?;
+            lines.property("UID", &uid)?;
+            lines.property("DTSTAMP", &stamp)?;
+            lines.property(
+                &format!("RECURRENCE-ID;TZID={tz}"),
+                &format_local(reschedule.date.and_time(group.start)),
+            )?;
+            write_times(
+                lines,
+                tz,
+                start,
+                Duration::minutes(reschedule.duration.0.into()),
+            )?;
+            if output.canceled.is_all() {
+                lines.property("STATUS", "CANCELLED")?;
+            }
+            write_details(
+                lines,
+                output,
+                Details {
+                    description: reschedule.description.or(group.details.description),
+                    ..group.details
+                },
+            )?;
+            lines.property("END", "VEVENT")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_times(
+    lines: &mut Lines<impl Write>,
+    tz: Tz,
+    start: NaiveDateTime,
+    duration: Duration,
+) -> io::Result<()> {
+    lines.property(&format!("DTSTART;TZID={tz}"), &format_local(start))?;
+    lines.property("DURATION", &format!("PT{}M", duration.num_minutes()))
+}
+
+fn write_details(
+    lines: &mut Lines<impl Write>,
+    output: &output::Event,
+    details: Details,
+) -> io::Result<()> {
+    lines.text("SUMMARY", details.name)?;
+    if let Some(description) = details.description {
+        lines.text("DESCRIPTION", description)?;
+    }
+    if let Some(web) = details.web {
+        lines.property("URL", web)?;
+    }
+    if let Some(world) = details.world {
+        lines.text("LOCATION", world)?;
+    }
+    if !output.tags.is_empty() {
+        let tags: Vec<_> = output.tags.iter().map(|t| escape(t)).collect();
+        lines.property("CATEGORIES", &tags.join(","))?;
+    }
+    Ok(())
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn format_local(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn format_utc(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.abs();
+    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes content lines, folding them at 75 octets as iCalendar requires.
+struct Lines<W>(W);
+
+impl<W: Write> Lines<W> {
+    fn property(&mut self, name: &str, value: &str) -> io::Result<()> {
+        let mut width = 0;
+        for part in [name, ":", value] {
+            for c in part.chars() {
+                if width + c.len_utf8() > 75 {
+                    self.0.write_all(b"\r\n ")?;
+                    width = 1;
+                }
+                self.0.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?;
+                width += c.len_utf8();
+            }
+        }
+        self.0.write_all(b"\r\n")
+    }
+
+    fn text(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.property(name, &escape(value))
+    }
+}
@@ -0,0 +1,158 @@
+//! Converting events from other calendars into event files.
+
+use std::{fs, path::Path};
+
+use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
+use miette::{Context, IntoDiagnostic};
+use toml_edit::{value, Array, ArrayOfTables, Document, Item, Table};
+
+use crate::{diagnostics, error::EventFileExists};
+
+pub mod csv;
+pub mod ics;
+
+/// An event read from another calendar.
+pub struct Imported {
+    pub name: String,
+    pub timezone: String,
+    pub start: NaiveTime,
+    pub duration: Duration,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub every: Option<u8>,
+    pub weeks: Vec<i8>,
+    /// If this is empty, the event is held every day.
+    pub days: Vec<Weekday>,
+    /// If this is not empty, the event is only held on these dates.
+    pub dates: Vec<NaiveDate>,
+    pub canceled: Vec<NaiveDate>,
+    pub reschedule: Vec<ImportedReschedule>,
+    pub description: Option<String>,
+    pub web: Option<String>,
+}
+
+pub struct ImportedReschedule {
+    pub date: NaiveDate,
+    pub to: Option<NaiveDate>,
+    pub start: Option<NaiveTime>,
+    pub duration: Option<Duration>,
+    pub description: Option<String>,
+}
+
+/// Saves an imported event as a new event file in `directory`. Existing files are never
+/// overwritten.
+pub fn save(directory: &Path, event: &Imported) -> miette::Result<()> {
+    let (file_name, named) = file_name(&event.name);
+    let path = directory.join(file_name);
+    if path.exists() {
+        diagnostics::report(EventFileExists { path });
+        return Ok(());
+    }
+
+    let mut document = Document::new();
+    if named {
+        document["name"] = value(&event.name);
+    }
+    write_event(&mut document, event);
+    fs::write(&path, document.to_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not save {}", path.display()))
+}
+
+/// Gets the name of the event file for an event, and whether the name needs to be written in the
+/// file because it can't be used as the file name.
+fn file_name(name: &str) -> (String, bool) {
+    let safe: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let safe = safe.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    let safe = if safe.is_empty() { "event" } else { safe };
+    (format!("{safe}.toml"), safe != name)
+}
+
+fn write_event(document: &mut Document, event: &Imported) {
+    document["timezone"] = value(&event.timezone);
+    document["start"] = value(event.start.format("%H:%M").to_string());
+    document["duration"] = value(format_duration(event.duration));
+    if let Some(start_date) = event.start_date {
+        document["start_date"] = value(start_date.to_string());
+    }
+    if let Some(end_date) = event.end_date {
+        document["end_date"] = value(end_date.to_string());
+    }
+    if let Some(every) = event.every {
+        document["every"] = value(format!("{every} weeks"));
+    }
+    if !event.weeks.is_empty() {
+        document["weeks"] = value(event.weeks.iter().map(|&w| i64::from(w)).collect::<Array>());
+    }
+    if !event.dates.is_empty() {
+        document["dates"] = value(dates(&event.dates));
+    }
+    if let Some(description) = &event.description {
+        document["description"] = value(description);
+    }
+    if let Some(web) = &event.web {
+        document["web"] = value(web);
+    }
+    if !event.canceled.is_empty() {
+        document["canceled"] = value(dates(&event.canceled));
+    }
+
+    if !event.days.is_empty() {
+        let mut days = Table::new();
+        days.set_implicit(true);
+        for &day in &event.days {
+            days.insert(day_name(day), Item::Table(Table::new()));
+        }
+        document["days"] = Item::Table(days);
+    }
+
+    if !event.reschedule.is_empty() {
+        let mut reschedule = ArrayOfTables::new();
+        for r in &event.reschedule {
+            let mut table = Table::new();
+            table["date"] = value(r.date.to_string());
+            if let Some(to) = r.to {
+                table["to"] = value(to.to_string());
+            }
+            if let Some(start) = r.start {
+                table["start"] = value(start.format("%H:%M").to_string());
+            }
+            if let Some(duration) = r.duration {
+                table["duration"] = value(format_duration(duration));
+            }
+            if let Some(description) = &r.description {
+                table["description"] = value(description);
+            }
+            reschedule.push(table);
+        }
+        document["reschedule"] = Item::ArrayOfTables(reschedule);
+    }
+}
+
+fn dates(dates: &[NaiveDate]) -> Array {
+    dates.iter().map(|d| d.to_string()).collect()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+pub(crate) fn day_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
@@ -0,0 +1,587 @@
+//! Importing events from a spreadsheet saved as CSV.
+//!
+//! The first row names the columns. Each following row is an event, which is matched to an
+//! existing event file by name. Empty cells leave the existing details unchanged.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::{Duration, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use miette::{Context, IntoDiagnostic};
+use toml_edit::{Array, Document, Item, Table, TableLike, Value};
+
+use super::{day_name, file_name, format_duration};
+use crate::{
+    collect_files, diagnostics,
+    error::{EventParseError, IgnoredProperty, InvalidProperty},
+    input::MAX_DURATION,
+    EventFile,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    Name,
+    TimeZone,
+    Start,
+    Duration,
+    Days,
+    World,
+    WorldName,
+    Group,
+    Discord,
+    Platforms,
+}
+
+impl Column {
+    fn from_header(header: &str) -> Option<Self> {
+        Some(
+            match header
+                .trim()
+                .to_lowercase()
+                .replace([' ', '-'], "_")
+                .as_str()
+            {
+                "name" => Column::Name,
+                "timezone" | "time_zone" => Column::TimeZone,
+                "start" => Column::Start,
+                "duration" => Column::Duration,
+                "days" => Column::Days,
+                "world" | "world_id" => Column::World,
+                "world_name" => Column::WorldName,
+                "group" => Column::Group,
+                "discord" => Column::Discord,
+                "platforms" => Column::Platforms,
+                _ => return None,
+            },
+        )
+    }
+}
+
+/// A cell of the CSV file.
+struct Cell {
+    value: String,
+    span: Range<usize>,
+}
+
+/// The details of an event read from a row.
+#[derive(Default)]
+struct Row {
+    timezone: Option<String>,
+    start: Option<NaiveTime>,
+    duration: Option<Duration>,
+    /// An empty list means every day.
+    days: Option<Vec<Weekday>>,
+    world: Option<String>,
+    world_name: Option<String>,
+    group: Option<String>,
+    discord: Option<String>,
+    platforms: Option<Vec<&'static str>>,
+}
+
+/// Creates or updates the event files in `input` from the rows of a CSV file.
+pub fn import(file: &Path, input: &Path) -> ExitCode {
+    let content = match fs::read_to_string(file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Reading {} failed.", file.display()))
+    {
+        Ok(content) => content,
+        Err(error) => {
+            diagnostics::report(error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let source = EventFile {
+        path: file,
+        content: Arc::new(content),
+    };
+    let invalid = |column: &str, reason: &str, span: Range<usize>| InvalidProperty {
+        property: column.to_owned(),
+        reason: reason.to_owned(),
+        src: (&source).into(),
+        location: span.into(),
+    };
+
+    let rows = match parse(&source.content) {
+        Ok(rows) => rows,
+        Err(span) => {
+            diagnostics::report(invalid("CSV", "the quoted cell is never closed", span));
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some((header, rows)) = rows.split_first() else {
+        return ExitCode::SUCCESS;
+    };
+    let columns: Vec<_> = header
+        .iter()
+        .map(|cell| {
+            let column = Column::from_header(&cell.value);
+            if column.is_none() {
+                diagnostics::report(IgnoredProperty {
+                    property: format!("The column {:?}", cell.value),
+                    src: (&source).into(),
+                    location: cell.span.clone().into(),
+                });
+            }
+            column
+        })
+        .collect();
+    let Some(name_column) = columns.iter().position(|&c| c == Some(Column::Name)) else {
+        let span = header.first().map_or(0..0, |c| c.span.clone());
+        diagnostics::report(invalid("header", "there is no name column", span));
+        return ExitCode::FAILURE;
+    };
+
+    let mut existing = existing_events(input);
+    let mut seen = BTreeSet::new();
+    for row in rows {
+        if row.iter().all(|c| c.value.trim().is_empty()) {
+            continue;
+        }
+        let Some(name) = row.get(name_column).filter(|c| !c.value.trim().is_empty()) else {
+            let span = row.first().map_or(0..0, |c| c.span.clone());
+            diagnostics::report(invalid("name", "the name is empty", span));
+            continue;
+        };
+        if !seen.insert(name.value.trim()) {
+            let reason = "the event is already in another row";
+            diagnostics::report(invalid("name", reason, name.span.clone()));
+            continue;
+        }
+
+        let mut details = Row::default();
+        let mut valid = true;
+        for (cell, &column) in row.iter().zip(&columns) {
+            let value = cell.value.trim();
+            let Some(column) = column.filter(|_| !value.is_empty()) else {
+                continue;
+            };
+            if let Err((header, reason)) = details.set(column, value) {
+                diagnostics::report(invalid(header, &reason, cell.span.clone()));
+                valid = false;
+            }
+        }
+        if !valid {
+            continue;
+        }
+
+        let name_cell = &row[name_column];
+        let name = name_cell.value.trim();
+        let (path, content) = match existing.remove(name) {
+            Some(path) => match fs::read_to_string(&path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Reading {} failed.", path.display()))
+            {
+                Ok(content) => (path, content),
+                Err(error) => {
+                    diagnostics::report(error);
+                    continue;
+                }
+            },
+            None => {
+                if details.timezone.is_none()
+                    || details.start.is_none()
+                    || details.duration.is_none()
+                {
+                    let reason = "new events need a timezone, start and duration";
+                    diagnostics::report(invalid("event", reason, name_cell.span.clone()));
+                    continue;
+                }
+                let (file_name, named) = file_name(name);
+                let path = input.join(&file_name);
+                if path.exists() {
+                    let reason = format!("{file_name} already belongs to another event");
+                    diagnostics::report(invalid("name", &reason, name_cell.span.clone()));
+                    continue;
+                }
+                let content = if named {
+                    format!("name = {}\n", Value::from(name))
+                } else {
+                    String::new()
+                };
+                (path, content)
+            }
+        };
+        let mut document: Document = match content
+            .parse()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Parsing {} failed.", path.display()))
+        {
+            Ok(document) => document,
+            Err(error) => {
+                diagnostics::report(error);
+                continue;
+            }
+        };
+        if let Err(reason) = details.check(&document) {
+            diagnostics::report(invalid("world", reason, name_cell.span.clone()));
+            continue;
+        }
+
+        details.apply(&mut document);
+        let updated = document.to_string();
+        if updated != content {
+            if let Err(error) = fs::write(&path, updated)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Could not save {}", path.display()))
+            {
+                diagnostics::report(error);
+            }
+        }
+    }
+
+    if diagnostics::errors() != 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+impl Row {
+    /// Reads a cell. Errors are the name of the column and what's wrong with the value.
+    fn set(&mut self, column: Column, value: &str) -> Result<(), (&'static str, String)> {
+        match column {
+            Column::Name => {}
+            Column::TimeZone => {
+                if Tz::from_str(value).is_err() {
+                    return Err(("timezone", format!("{value} is not an IANA time zone")));
+                }
+                self.timezone = Some(value.to_owned());
+            }
+            Column::Start => {
+                let start = NaiveTime::parse_from_str(value, "%H:%M")
+                    .map_err(|_| ("start", "times must be written like 21:30".to_owned()))?;
+                self.start = Some(start);
+            }
+            Column::Duration => {
+                let minutes = value
+                    .split_once(':')
+                    .and_then(|(h, m)| Some((h.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+                    .filter(|&(_, m)| m < 60)
+                    .and_then(|(h, m)| h.checked_mul(60)?.checked_add(m))
+                    .ok_or(("duration", "durations must be written like 1:30".to_owned()))?;
+                if minutes > u32::from(MAX_DURATION) {
+                    let reason = "durations must not be longer than 168:00".to_owned();
+                    return Err(("duration", reason));
+                }
+                let duration = Duration::minutes(i64::from(minutes));
+                self.duration = Some(duration);
+            }
+            Column::Days => {
+                let days = if value.eq_ignore_ascii_case("daily") {
+                    Vec::new()
+                } else {
+                    split_list(value)
+                        .map(|d| {
+                            parse_day(d).ok_or(("days", format!("{d} is not a day of the week")))
+                        })
+                        .collect::<Result<_, _>>()?
+                };
+                self.days = Some(days);
+            }
+            Column::World => self.world = Some(value.to_owned()),
+            Column::WorldName => self.world_name = Some(value.to_owned()),
+            Column::Group => self.group = Some(value.to_owned()),
+            Column::Discord => self.discord = Some(value.to_owned()),
+            Column::Platforms => {
+                let platforms = split_list(value)
+                    .map(|p| match p.to_lowercase().as_str() {
+                        "pc" => Ok("pc"),
+                        "quest" => Ok("quest"),
+                        _ => Err(("platforms", format!("{p} is not pc or quest"))),
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.platforms = Some(platforms);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the details in an event file, keeping everything else as it is.
+    fn apply(&self, document: &mut Document) {
+        let root = document.as_table_mut();
+        if let Some(timezone) = &self.timezone {
+            set(root, "timezone", timezone.as_str());
+        }
+        if let Some(start) = self.start {
+            set(root, "start", start.format("%H:%M").to_string());
+        }
+        if let Some(duration) = self.duration {
+            set(root, "duration", format_duration(duration));
+        }
+        if let Some(group) = &self.group {
+            set(root, "group", group.as_str());
+        }
+        if let Some(discord) = &self.discord {
+            set(root, "discord", discord.as_str());
+        }
+        if let Some(platforms) = &self.platforms {
+            set(
+                root,
+                "platforms",
+                platforms.iter().copied().collect::<Array>(),
+            );
+        }
+
+        if self.world.is_some() || self.world_name.is_some() {
+            let world = root
+                .entry("world")
+                .or_insert_with(|| Item::Table(Table::new()));
+            if let Some(world) = world.as_table_like_mut() {
+                if let Some(id) = &self.world {
+                    set(world, "id", id.as_str());
+                }
+                if let Some(name) = &self.world_name {
+                    set(world, "name", name.as_str());
+                }
+            }
+        }
+
+        if let Some(days) = &self.days {
+            if days.is_empty() {
+                root.remove("days");
+            } else {
+                let table = root.entry("days").or_insert_with(|| {
+                    let mut table = Table::new();
+                    table.set_implicit(true);
+                    Item::Table(table)
+                });
+                if let Some(table) = table.as_table_like_mut() {
+                    let names: Vec<_> = days.iter().map(|&d| day_name(d)).collect();
+                    let removed: Vec<_> = table
+                        .iter()
+                        .map(|(k, _)| k.to_owned())
+                        .filter(|k| !names.contains(&k.as_str()))
+                        .collect();
+                    for day in removed {
+                        table.remove(&day);
+                    }
+                    // Days that are already there keep their overridden details.
+                    for name in names {
+                        if !table.contains_key(name) {
+                            table.insert(name, Item::Table(Table::new()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the details that have to be in the event file together.
+    fn check(&self, document: &Document) -> Result<(), &'static str> {
+        let world = document.get("world").and_then(Item::as_table_like);
+        let has = |key| world.is_some_and(|w| w.contains_key(key));
+        if self.world.is_some() && self.world_name.is_none() && !has("name") {
+            return Err("the world needs a name in the world_name column");
+        }
+        if self.world_name.is_some() && self.world.is_none() && !has("id") {
+            return Err("the world needs an ID in the world column");
+        }
+        Ok(())
+    }
+}
+
+/// Sets a value, keeping the comments around the old value.
+fn set(table: &mut dyn TableLike, key: &str, value: impl Into<Value>) {
+    let mut value = value.into();
+    if let Some(old) = table.get(key).and_then(Item::as_value) {
+        *value.decor_mut() = old.decor().clone();
+    }
+    table.insert(key, Item::Value(value));
+}
+
+/// Finds the event files that already exist, by event name.
+fn existing_events(input: &Path) -> HashMap<String, PathBuf> {
+    let mut files = BTreeSet::new();
+    collect_files(input, &mut files);
+    let mut events = HashMap::new();
+    for path in files
+        .into_iter()
+        .filter(|f| f.extension() == Some(OsStr::new("toml")) && *f != input.join("meta.toml"))
+    {
+        let content = match fs::read_to_string(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Reading {} failed.", path.display()))
+        {
+            Ok(content) => content,
+            Err(error) => {
+                diagnostics::report(error);
+                continue;
+            }
+        };
+        let file = EventFile {
+            path: &path,
+            content: Arc::new(content),
+        };
+        let document = match toml::Table::from_str(&file.content)
+            .map_err(|error| EventParseError::new(error, &file))
+            .wrap_err_with(|| format!("Parsing {} failed.", path.display()))
+        {
+            Ok(document) => document,
+            Err(error) => {
+                diagnostics::report(error);
+                continue;
+            }
+        };
+        let name = match document.get("name").and_then(toml::Value::as_str) {
+            Some(name) => name.to_owned(),
+            None => match path.file_stem().and_then(OsStr::to_str) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            },
+        };
+        events.insert(name, path);
+    }
+    events
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split([',', ';', '/', ' '])
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+fn parse_day(day: &str) -> Option<Weekday> {
+    let day = day.to_lowercase();
+    [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+    .into_iter()
+    .find(|&d| day.len() >= 3 && day_name(d).starts_with(&day))
+}
+
+/// Reads the cells of a CSV file. If a quoted cell is never closed, its span is the error.
+fn parse(content: &str) -> Result<Vec<Vec<Cell>>, Range<usize>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut chars = content.char_indices().peekable();
+    // Spreadsheets often save a byte order mark.
+    if content.starts_with('\u{feff}') {
+        chars.next();
+    }
+    loop {
+        let start = chars.peek().map_or(content.len(), |&(i, _)| i);
+        let mut value = String::new();
+        if chars.next_if(|&(_, c)| c == '"').is_some() {
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => {
+                        if chars.next_if(|&(_, c)| c == '"').is_some() {
+                            value.push('"');
+                        } else {
+                            break;
+                        }
+                    }
+                    Some((_, c)) => value.push(c),
+                    None => return Err(start..content.len()),
+                }
+            }
+            // Anything between the closing quote and the separator is kept as is.
+            while let Some((_, c)) = chars.next_if(|&(_, c)| !matches!(c, ',' | '\n' | '\r')) {
+                value.push(c);
+            }
+        } else {
+            while let Some((_, c)) = chars.next_if(|&(_, c)| !matches!(c, ',' | '\n' | '\r')) {
+                value.push(c);
+            }
+        }
+        let end = chars.peek().map_or(content.len(), |&(i, _)| i);
+        row.push(Cell {
+            value,
+            span: start..end,
+        });
+
+        match chars.next() {
+            Some((_, ',')) => {}
+            Some((_, '\r')) => {
+                chars.next_if(|&(_, c)| c == '\n');
+                rows.push(std::mem::take(&mut row));
+            }
+            Some((_, '\n')) => rows.push(std::mem::take(&mut row)),
+            Some(_) => unreachable!(),
+            None => {
+                if !(row.len() == 1 && row[0].value.is_empty()) {
+                    rows.push(row);
+                }
+                return Ok(rows);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `content` and returns the values of each row.
+    fn values(content: &str) -> Vec<Vec<String>> {
+        parse(content)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.into_iter().map(|cell| cell.value).collect())
+            .collect()
+    }
+
+    #[test]
+    fn reads_quoted_cells() {
+        assert_eq!(
+            values("name,world\n\"Chill, Relax\",\"The \"\"Bar\"\"\"\n"),
+            [["name", "world"], ["Chill, Relax", "The \"Bar\""]]
+        );
+    }
+
+    #[test]
+    fn keeps_newlines_in_quoted_cells() {
+        assert_eq!(
+            values("name,description\nA,\"first\nsecond\"\nB,third\n"),
+            [
+                ["name", "description"],
+                ["A", "first\nsecond"],
+                ["B", "third"]
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_byte_order_mark() {
+        let rows = parse("\u{feff}name\nA").unwrap();
+        assert_eq!(rows[0][0].value, "name");
+        assert_eq!(rows[0][0].span, 3..7);
+        assert_eq!(rows[1][0].value, "A");
+    }
+
+    #[test]
+    fn splits_rows_on_crlf() {
+        assert_eq!(
+            values("name,start\r\nA,20:00\r\nB,21:00\r\n"),
+            [["name", "start"], ["A", "20:00"], ["B", "21:00"]]
+        );
+    }
+
+    #[test]
+    fn spans_cover_the_quotes() {
+        let rows = parse("a,\"b\"\n").unwrap();
+        assert_eq!(rows[0][0].span, 0..1);
+        assert_eq!(rows[0][1].span, 2..5);
+    }
+
+    #[test]
+    fn reports_unclosed_quotes() {
+        assert_eq!(parse("name\nA,\"open\nB\n").err(), Some(7..15));
+    }
+}
@@ -0,0 +1,704 @@
+//! Importing events from iCalendar files.
+
+use std::{
+    collections::HashMap, fs, ops::Range, path::Path, process::ExitCode, str::FromStr, sync::Arc,
+};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use miette::{Context, IntoDiagnostic};
+
+use super::{Imported, ImportedReschedule};
+use crate::{
+    diagnostics,
+    error::{IgnoredProperty, InvalidProperty, UnsupportedRule},
+    input::Week,
+    EventFile,
+};
+
+/// Converts the events in an iCalendar file to event files in `directory`.
+pub fn import(file: &Path, directory: &Path) -> ExitCode {
+    let content = match fs::read_to_string(file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Reading {} failed.", file.display()))
+    {
+        Ok(content) => content,
+        Err(error) => {
+            diagnostics::report(error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let source = EventFile {
+        path: file,
+        content: Arc::new(content),
+    };
+    if let Err(error) = fs::create_dir_all(directory)
+        .into_diagnostic()
+        .wrap_err("Could not create output directory")
+    {
+        diagnostics::report(error);
+        return ExitCode::FAILURE;
+    }
+
+    let calendar = Calendar::parse(&source.content);
+    let default_tz = calendar
+        .properties
+        .iter()
+        .find(|p| p.name == "X-WR-TIMEZONE")
+        .and_then(|p| Tz::from_str(&p.value).ok());
+
+    // Changes to single occurrences are separate events with the same UID.
+    let mut overrides = HashMap::<&str, Vec<&Component>>::new();
+    for event in &calendar.events {
+        if let (Some(uid), Some(_)) = (event.get("UID"), event.get("RECURRENCE-ID")) {
+            overrides.entry(&uid.value).or_default().push(event);
+        }
+    }
+
+    let mut names = HashMap::<String, usize>::new();
+    for event in &calendar.events {
+        if event.get("RECURRENCE-ID").is_some() {
+            continue;
+        }
+        let overrides = event
+            .get("UID")
+            .and_then(|uid| overrides.get(uid.value.as_str()))
+            .map_or(&[][..], Vec::as_slice);
+        let converter = Converter {
+            source: &source,
+            default_tz,
+        };
+        let Some(mut imported) = converter.convert(event, overrides) else {
+            continue;
+        };
+        // Event names must be unique, but calendars often have several events with the same name.
+        let count = names.entry(imported.name.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            imported.name = format!("{} ({count})", imported.name);
+        }
+        if let Err(error) = super::save(directory, &imported) {
+            diagnostics::report(error);
+        }
+    }
+
+    if diagnostics::errors() != 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// A content line, after unfolding.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+    span: Range<usize>,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct Component {
+    properties: Vec<Property>,
+}
+
+impl Component {
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+}
+
+struct Calendar {
+    /// The properties of the calendar itself.
+    properties: Vec<Property>,
+    events: Vec<Component>,
+}
+
+impl Calendar {
+    fn parse(content: &str) -> Self {
+        let mut calendar = Calendar {
+            properties: Vec::new(),
+            events: Vec::new(),
+        };
+        // The components that are open, and the properties of the innermost event.
+        let mut stack = Vec::new();
+        let mut event = None;
+        for property in unfold(content).filter_map(|(line, span)| parse_line(&line, span)) {
+            match property.name.as_str() {
+                "BEGIN" => {
+                    if property.value.eq_ignore_ascii_case("VEVENT") {
+                        event = Some(Component {
+                            properties: Vec::new(),
+                        });
+                    }
+                    stack.push(property.value.to_ascii_uppercase());
+                }
+                "END" => {
+                    if stack.pop().as_deref() == Some("VEVENT") {
+                        calendar.events.extend(event.take());
+                    }
+                }
+                _ => match stack.last().map(String::as_str) {
+                    Some("VEVENT") => {
+                        if let Some(event) = &mut event {
+                            event.properties.push(property);
+                        }
+                    }
+                    Some("VCALENDAR") => calendar.properties.push(property),
+                    _ => {}
+                },
+            }
+        }
+        calendar
+    }
+}
+
+/// Joins folded lines, keeping the span of the original lines.
+fn unfold(content: &str) -> impl Iterator<Item = (String, Range<usize>)> + '_ {
+    let mut lines = Vec::<(String, Range<usize>)>::new();
+    let mut offset = 0;
+    for raw in content.split_inclusive('\n') {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.trim_end_matches(['\r', '\n']);
+        let end = start + line.len();
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((last, span))) => {
+                last.push_str(rest);
+                span.end = end;
+            }
+            _ => lines.push((line.to_owned(), start..end)),
+        }
+    }
+    lines.into_iter().filter(|(line, _)| !line.is_empty())
+}
+
+fn parse_line(line: &str, span: Range<usize>) -> Option<Property> {
+    // The value starts at the first colon that isn't in a quoted parameter value.
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_owned()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_owned(),
+        span,
+    })
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => {}
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+struct Converter<'a> {
+    source: &'a EventFile<'a>,
+    /// The time zone for times that are in UTC or have no time zone.
+    default_tz: Option<Tz>,
+}
+
+impl Converter<'_> {
+    fn convert(&self, event: &Component, overrides: &[&Component]) -> Option<Imported> {
+        let name = event
+            .get("SUMMARY")
+            .map(|p| unescape(&p.value))
+            .filter(|n| !n.trim().is_empty());
+        let Some(start) = event.get("DTSTART") else {
+            diagnostics::report(self.invalid(event.properties.first()?, "DTSTART is missing"));
+            return None;
+        };
+        let Some(name) = name else {
+            diagnostics::report(self.invalid(start, "SUMMARY is missing"));
+            return None;
+        };
+        let tz = self.time_zone(start)?;
+        let start_time = self.date_time(start, tz)?;
+
+        let duration = if let Some(duration) = event.get("DURATION") {
+            self.duration(duration)?
+        } else if let Some(end) = event.get("DTEND") {
+            self.date_time(end, tz)? - start_time
+        } else if start.param("VALUE") == Some("DATE") {
+            Duration::days(1)
+        } else {
+            Duration::zero()
+        };
+        if duration < Duration::zero() || Duration::weeks(1) < duration {
+            diagnostics::report(UnsupportedRule {
+                reason: "events must be shorter than a week".into(),
+                src: self.source.into(),
+                location: start.span.clone().into(),
+            });
+            return None;
+        }
+
+        let mut imported = Imported {
+            name,
+            timezone: tz.name().to_owned(),
+            start: start_time.time(),
+            duration,
+            start_date: None,
+            end_date: None,
+            every: None,
+            weeks: Vec::new(),
+            days: Vec::new(),
+            dates: Vec::new(),
+            canceled: Vec::new(),
+            reschedule: Vec::new(),
+            description: event.get("DESCRIPTION").map(|p| unescape(&p.value)),
+            web: event.get("URL").map(|p| p.value.clone()),
+        };
+
+        match event.get("RRULE") {
+            Some(rule) => {
+                self.rule(rule, start_time.date(), tz, &mut imported)?;
+                for property in event.get_all("RDATE") {
+                    diagnostics::report(self.ignored(property));
+                }
+            }
+            None => {
+                imported.dates.push(start_time.date());
+                for property in event.get_all("RDATE") {
+                    imported.dates.extend(self.date_list(property, tz));
+                }
+                imported.dates.sort_unstable();
+                imported.dates.dedup();
+            }
+        }
+
+        for property in event.get_all("EXDATE") {
+            let dates = self.date_list(property, tz);
+            if imported.dates.is_empty() {
+                imported.canceled.extend(dates);
+            } else {
+                imported.dates.retain(|d| !dates.contains(d));
+            }
+        }
+
+        for &change in overrides {
+            let Some(id) = change.get("RECURRENCE-ID") else {
+                continue;
+            };
+            let Some(date) = self.date_time(id, tz).map(|d| d.date()) else {
+                continue;
+            };
+            let status = change.get("STATUS").map(|p| p.value.to_ascii_uppercase());
+            if status.as_deref() == Some("CANCELLED") {
+                imported.canceled.push(date);
+                continue;
+            }
+            if !imported.dates.is_empty() {
+                diagnostics::report(self.ignored(id));
+                continue;
+            }
+            let Some(new_start) = change.get("DTSTART").and_then(|p| self.date_time(p, tz)) else {
+                continue;
+            };
+            let new_duration = if let Some(duration) = change.get("DURATION") {
+                self.duration(duration)
+            } else if let Some(end) = change.get("DTEND") {
+                self.date_time(end, tz).map(|end| end - new_start)
+            } else {
+                None
+            };
+            imported.reschedule.push(ImportedReschedule {
+                date,
+                to: Some(new_start.date()).filter(|&d| d != date),
+                start: Some(new_start.time()).filter(|&t| t != imported.start),
+                duration: new_duration.filter(|&d| d != imported.duration),
+                description: change
+                    .get("DESCRIPTION")
+                    .map(|p| unescape(&p.value))
+                    .filter(|d| Some(d) != imported.description.as_ref()),
+            });
+        }
+        imported.canceled.sort_unstable();
+        imported.canceled.dedup();
+        imported.reschedule.sort_unstable_by_key(|r| r.date);
+
+        Some(imported)
+    }
+
+    /// Converts a recurrence rule to the schedule of the event.
+    fn rule(
+        &self,
+        property: &Property,
+        mut start: NaiveDate,
+        tz: Tz,
+        imported: &mut Imported,
+    ) -> Option<()> {
+        let unsupported = |reason: String| {
+            diagnostics::report(UnsupportedRule {
+                reason,
+                src: self.source.into(),
+                location: property.span.clone().into(),
+            });
+        };
+
+        let mut freq = None;
+        let mut interval = 1u32;
+        let mut by_day = Vec::new();
+        let mut by_set_pos = Vec::new();
+        let mut until = None;
+        let mut count = None;
+        let mut week_start = Weekday::Mon;
+        for part in property.value.split(';') {
+            let Some((key, v)) = part.split_once('=') else {
+                continue;
+            };
+            let parsed = match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(v.to_ascii_uppercase());
+                    Some(())
+                }
+                "INTERVAL" => v.parse().ok().map(|v| interval = v),
+                "BYDAY" => v
+                    .split(',')
+                    .map(parse_by_day)
+                    .collect::<Option<Vec<_>>>()
+                    .map(|v| by_day = v),
+                "BYSETPOS" => v
+                    .split(',')
+                    .map(|v| v.parse::<i8>().ok())
+                    .collect::<Option<Vec<_>>>()
+                    .map(|v| by_set_pos = v),
+                "UNTIL" => parse_date_time(v).map(|(time, utc)| {
+                    until = Some(if utc {
+                        tz.from_utc_datetime(&time).date_naive()
+                    } else {
+                        time.date()
+                    })
+                }),
+                "COUNT" => v.parse::<u32>().ok().map(|v| count = Some(v)),
+                "WKST" => parse_weekday(v).map(|v| week_start = v),
+                _ => {
+                    unsupported(format!("{key} can't be converted"));
+                    return None;
+                }
+            };
+            if parsed.is_none() {
+                unsupported(format!("{key}={v} is invalid"));
+                return None;
+            }
+        }
+
+        let plain_days = || -> Option<Vec<Weekday>> {
+            by_day
+                .iter()
+                .map(|&(ordinal, day)| ordinal.is_none().then_some(day))
+                .collect()
+        };
+        match freq.as_deref() {
+            Some("DAILY") if interval == 1 && by_set_pos.is_empty() => {
+                let Some(days) = plain_days() else {
+                    unsupported("BYDAY can't have weeks in daily rules".into());
+                    return None;
+                };
+                imported.days = days;
+            }
+            Some("WEEKLY") if by_set_pos.is_empty() => {
+                let Some(mut days) = plain_days() else {
+                    unsupported("BYDAY can't have weeks in weekly rules".into());
+                    return None;
+                };
+                if days.is_empty() {
+                    days.push(start.weekday());
+                }
+                let Ok(every) = u8::try_from(interval) else {
+                    unsupported(format!("INTERVAL={interval} is more than 255 weeks"));
+                    return None;
+                };
+                if every > 1 {
+                    // Weeks are counted from the start date instead of from WKST, so the start
+                    // date becomes the first occurrence. That only counts the same weeks if no
+                    // day of the first week comes before it.
+                    let Some(first) = first_occurrence(start, every, week_start, &days) else {
+                        unsupported(format!(
+                            "repeating every {interval} weeks can't be converted when DTSTART is \
+                             after the first BYDAY of its week",
+                        ));
+                        return None;
+                    };
+                    start = first;
+                    imported.every = Some(every);
+                }
+                imported.days = days;
+            }
+            Some("MONTHLY") if interval == 1 => {
+                let mut weeks = Vec::new();
+                let mut days = Vec::new();
+                for &(ordinal, day) in &by_day {
+                    weeks.extend(ordinal);
+                    days.push(day);
+                }
+                if weeks.is_empty() {
+                    weeks = by_set_pos.clone();
+                } else if !by_set_pos.is_empty() {
+                    unsupported("BYSETPOS can't be used with weeks in BYDAY".into());
+                    return None;
+                }
+                weeks.sort_unstable();
+                weeks.dedup();
+                days.sort_unstable_by_key(|d| d.num_days_from_monday());
+                days.dedup();
+                // Every day has to be on every week.
+                let complete = if by_set_pos.is_empty() {
+                    by_day.iter().all(|(o, _)| o.is_some())
+                        && weeks.len() * days.len() == by_day.len()
+                } else {
+                    days.len() == 1
+                };
+                if weeks.is_empty() || days.is_empty() || !complete {
+                    unsupported("monthly rules need the same weeks for every day".into());
+                    return None;
+                }
+                if let Some(&week) = weeks.iter().find(|&&w| !Week(w).is_valid()) {
+                    unsupported(format!("week {week} is not between 1 and 5 or -1 and -5"));
+                    return None;
+                }
+                imported.weeks = weeks;
+                imported.days = days;
+            }
+            Some(freq) => {
+                unsupported(format!("FREQ={freq} with these options can't be converted"));
+                return None;
+            }
+            None => {
+                unsupported("FREQ is missing".into());
+                return None;
+            }
+        }
+
+        imported.start_date = Some(start);
+        imported.end_date = match count {
+            Some(count) => last_occurrence(imported, start, count),
+            None => until,
+        };
+        Some(())
+    }
+
+    fn time_zone(&self, property: &Property) -> Option<Tz> {
+        match property.param("TZID") {
+            Some(id) => match Tz::from_str(id.trim_start_matches('/')) {
+                Ok(tz) => Some(tz),
+                Err(_) => {
+                    diagnostics::report(
+                        self.invalid(property, &format!("{id} is not an IANA time zone")),
+                    );
+                    None
+                }
+            },
+            None => Some(self.default_tz.unwrap_or(Tz::Etc__UTC)),
+        }
+    }
+
+    /// Gets a date or time in the event's time zone.
+    fn date_time(&self, property: &Property, tz: Tz) -> Option<NaiveDateTime> {
+        let Some((time, utc)) = parse_date_time(&property.value) else {
+            diagnostics::report(self.invalid(property, "the date is invalid"));
+            return None;
+        };
+        Some(self.localize(property, time, utc, tz))
+    }
+
+    fn date_list(&self, property: &Property, tz: Tz) -> Vec<NaiveDate> {
+        property
+            .value
+            .split(',')
+            .filter_map(|v| match parse_date_time(v) {
+                Some((time, utc)) => Some(self.localize(property, time, utc, tz).date()),
+                None => {
+                    diagnostics::report(self.invalid(property, "the date is invalid"));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn localize(
+        &self,
+        property: &Property,
+        time: NaiveDateTime,
+        utc: bool,
+        tz: Tz,
+    ) -> NaiveDateTime {
+        let from = if utc {
+            Tz::UTC
+        } else if property.param("TZID").is_some() {
+            self.time_zone(property).unwrap_or(tz)
+        } else {
+            tz
+        };
+        match from.from_local_datetime(&time).earliest() {
+            Some(time) => time.with_timezone(&tz).naive_local(),
+            None => time,
+        }
+    }
+
+    fn duration(&self, property: &Property) -> Option<Duration> {
+        let duration = parse_duration(&property.value);
+        if duration.is_none() {
+            diagnostics::report(self.invalid(property, "the duration is invalid"));
+        }
+        duration
+    }
+
+    fn invalid(&self, property: &Property, reason: &str) -> InvalidProperty {
+        InvalidProperty {
+            property: property.name.clone(),
+            reason: reason.to_owned(),
+            src: self.source.into(),
+            location: property.span.clone().into(),
+        }
+    }
+
+    fn ignored(&self, property: &Property) -> IgnoredProperty {
+        IgnoredProperty {
+            property: property.name.clone(),
+            src: self.source.into(),
+            location: property.span.clone().into(),
+        }
+    }
+}
+
+/// Finds the first occurrence of a rule that repeats every few weeks, where the weeks start on
+/// `week_start`. There is none if it isn't the first of the rule's days in its week.
+fn first_occurrence(
+    start: NaiveDate,
+    every: u8,
+    week_start: Weekday,
+    days: &[Weekday],
+) -> Option<NaiveDate> {
+    let offset = |day: Weekday| {
+        i64::from((7 + day.num_days_from_monday() - week_start.num_days_from_monday()) % 7)
+    };
+    let week = start - Duration::days(offset(start.weekday()));
+    let first = start
+        .iter_days()
+        .take(7 * (usize::from(every) + 1))
+        .find(|&date| {
+            days.contains(&date.weekday())
+                && (date - week).num_days().div_euclid(7) % i64::from(every) == 0
+        })?;
+    days.iter()
+        .all(|&day| offset(first.weekday()) <= offset(day))
+        .then_some(first)
+}
+
+/// Finds the date of the last occurrence of a rule that repeats `count` times.
+fn last_occurrence(imported: &Imported, start: NaiveDate, count: u32) -> Option<NaiveDate> {
+    let every = i64::from(imported.every.unwrap_or(1));
+    start
+        .iter_days()
+        .take(366 * 20)
+        .filter(|&date| {
+            (date - start).num_days().div_euclid(7) % every == 0
+                && (imported.days.is_empty() || imported.days.contains(&date.weekday()))
+                && (imported.weeks.is_empty()
+                    || imported.weeks.iter().any(|&w| Week(w).contains(date)))
+        })
+        .nth(count.checked_sub(1)? as usize)
+}
+
+/// Parses a date or date-time value, and whether it's in UTC.
+fn parse_date_time(value: &str) -> Option<(NaiveDateTime, bool)> {
+    let (value, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let time = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(time) => time,
+        Err(_) => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_time(NaiveTime::MIN),
+    };
+    Some((time, utc))
+}
+
+/// Parses a duration like `PT1H30M` or `P1D`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => time = true,
+            _ => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n.checked_mul(match (c, time) {
+                    ('W', false) => 7 * 24 * 60 * 60,
+                    ('D', false) => 24 * 60 * 60,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                })?;
+            }
+        }
+    }
+    number.is_empty().then(|| Duration::seconds(seconds))
+}
+
+/// Parses a BYDAY value like `MO` or `-1FR`.
+fn parse_by_day(value: &str) -> Option<(Option<i8>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let day = parse_weekday(value.get(split..)?)?;
+    let ordinal = match &value[..split] {
+        "" => None,
+        ordinal => Some(ordinal.strip_prefix('+').unwrap_or(ordinal).parse().ok()?),
+    };
+    Some((ordinal, day))
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    Some(match value.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
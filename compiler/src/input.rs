@@ -1,54 +1,59 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
 
-use chrono::{Duration, NaiveDate, NaiveTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
 use serde::{
-    de::{Error, Visitor},
-    Deserialize, Deserializer,
+    de::{value::MapAccessDeserializer, Error, Visitor},
+    Deserialize, Deserializer, Serialize,
 };
 use smallvec::{smallvec, SmallVec};
 use toml::Spanned;
 
-use crate::{output::Minutes, Language, Platform, User, World};
+use crate::{diagnostics::Level, output::Minutes, Language, Platform, User, World};
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Event<'a> {
     #[serde(borrow, flatten)]
     pub info: EventInfo<'a>,
-    pub start_date: Option<NaiveDate>,
-    pub end_date: Option<NaiveDate>,
+    /// A base file with details that are shared between events.
+    #[serde(borrow)]
+    pub extends: Option<Spanned<Cow<'a, str>>>,
+    pub start_date: Option<Spanned<NaiveDate>>,
+    pub end_date: Option<Spanned<NaiveDate>>,
+    pub every: Option<Spanned<Every>>,
+    /// The weeks of the month when the event is held.
+    pub weeks: Option<Spanned<Vec<Spanned<Week>>>>,
     #[serde(borrow)]
     pub timezone: Spanned<Cow<'a, str>>,
     pub start: Time<NaiveTime>,
-    pub duration: Time<Duration>,
+    pub duration: Spanned<Time<Duration>>,
     #[serde(default = "default_platforms")]
     pub platforms: SmallVec<[Platform; 2]>,
-    #[serde(borrow, default = "default_days")]
-    pub days: EventDays<'a>,
+    /// If there are neither days nor dates, the event is held every day.
+    #[serde(borrow, default)]
+    pub days: Option<EventDays<'a>>,
+    /// Special events are only held on these dates.
+    pub dates: Option<Spanned<Vec<Spanned<SpecialDate>>>>,
     #[serde(borrow, default)]
     pub languages: HashMap<Language, EventLanguage<'a>>,
     #[serde(default = "DateSet::all")]
     pub confirmed: DateSet,
     #[serde(default = "DateSet::none")]
     pub canceled: DateSet,
+    #[serde(borrow, default)]
+    pub reschedule: Vec<Spanned<Reschedule<'a>>>,
+    /// Tags defined in the meta file.
+    #[serde(borrow, default)]
+    pub tags: Vec<Spanned<Cow<'a, str>>>,
 }
 
 fn default_platforms() -> SmallVec<[Platform; 2]> {
     smallvec![Platform::Pc]
 }
 
-fn default_days() -> EventDays<'static> {
-    EventDays {
-        monday: Some(EventDay::default()),
-        tuesday: Some(EventDay::default()),
-        wednesday: Some(EventDay::default()),
-        thursday: Some(EventDay::default()),
-        friday: Some(EventDay::default()),
-        saturday: Some(EventDay::default()),
-        sunday: Some(EventDay::default()),
-    }
-}
-
 #[derive(Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct EventInfo<'a> {
@@ -72,7 +77,6 @@ pub struct EventInfo<'a> {
     pub join: Vec<User<'a>>,
     #[serde(borrow)]
     pub world: Option<World<'a>>,
-    pub weeks: Option<SmallVec<[u8; 5]>>,
 }
 
 #[derive(Deserialize)]
@@ -94,6 +98,20 @@ pub struct EventDays<'a> {
     pub sunday: Option<EventDay<'a>>,
 }
 
+impl<'a> EventDays<'a> {
+    pub fn get(&self, weekday: Weekday) -> Option<&EventDay<'a>> {
+        match weekday {
+            Weekday::Mon => self.monday.as_ref(),
+            Weekday::Tue => self.tuesday.as_ref(),
+            Weekday::Wed => self.wednesday.as_ref(),
+            Weekday::Thu => self.thursday.as_ref(),
+            Weekday::Fri => self.friday.as_ref(),
+            Weekday::Sat => self.saturday.as_ref(),
+            Weekday::Sun => self.sunday.as_ref(),
+        }
+    }
+}
+
 #[derive(Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct EventDay<'a> {
@@ -122,7 +140,7 @@ impl<'de> Deserialize<'de> for Time<NaiveTime> {
         D: Deserializer<'de>,
     {
         match NaiveTime::default()
-            .overflowing_add_signed(Time::<Duration>::deserialize(deserializer)?.0)
+            .overflowing_add_signed(Duration::minutes(deserialize_minutes(deserializer)?.into()))
         {
             (time, 0) => Ok(Time(time)),
             (_, _) => Err(D::Error::custom("Time must be less than 24:00")),
@@ -130,50 +148,71 @@ impl<'de> Deserialize<'de> for Time<NaiveTime> {
     }
 }
 
+/// The longest an event can last. Anything longer would overlap the same day next week.
+pub const MAX_DURATION: u16 = 7 * 24 * 60;
+
 impl<'de> Deserialize<'de> for Time<Duration> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(untagged)]
-        enum RawTime<'a> {
-            #[serde(borrow)]
-            String(Cow<'a, str>),
-            Minutes(u16),
-            Time(toml::value::Datetime),
+        let minutes = deserialize_minutes(deserializer)?;
+        if minutes > MAX_DURATION {
+            return Err(D::Error::custom("Duration must not be longer than 168:00"));
         }
+        Ok(Time(Duration::minutes(minutes.into())))
+    }
+}
+
+/// Parses `H:MM`, a number of minutes or a toml local time into a number of minutes.
+fn deserialize_minutes<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTime<'a> {
+        #[serde(borrow)]
+        String(Cow<'a, str>),
+        Minutes(u16),
+        Time(toml::value::Datetime),
+    }
 
-        let raw = RawTime::deserialize(deserializer)?;
-        let minutes = match raw {
-            RawTime::String(v) => {
-                if let Some((hours, minutes)) = v.split_once(':') {
-                    let hours: u16 = hours.parse().map_err(D::Error::custom)?;
-                    let minutes: u16 = minutes.parse().map_err(D::Error::custom)?;
-                    hours * 60 + minutes
-                } else {
-                    v.parse().map_err(D::Error::custom)?
+    let raw = RawTime::deserialize(deserializer)?;
+    let minutes = match raw {
+        RawTime::String(v) => {
+            if let Some((hours, minutes)) = v.split_once(':') {
+                let hours: u16 = hours.parse().map_err(D::Error::custom)?;
+                let minutes: u16 = minutes.parse().map_err(D::Error::custom)?;
+                if minutes >= 60 {
+                    return Err(D::Error::custom("Minutes must be less than 60"));
                 }
+                hours
+                    .checked_mul(60)
+                    .and_then(|h| h.checked_add(minutes))
+                    .ok_or_else(|| D::Error::custom("Time is too long"))?
+            } else {
+                v.parse().map_err(D::Error::custom)?
             }
-            RawTime::Minutes(minutes) => minutes,
-            RawTime::Time(time) => {
-                if time.date.is_some() {
-                    return Err(D::Error::custom("Time should not have a date"));
-                }
-                if time.offset.is_some() {
-                    return Err(D::Error::custom("Time should not have an offset"));
-                }
-                let Some(time) = time.time else {
-                    return Err(D::Error::custom("Time must contain a time"));
-                };
-                if time.second != 0 || time.nanosecond != 0 {
-                    return Err(D::Error::custom("Time must contain whole minutes"));
-                }
-                time.hour as u16 * 60 + time.minute as u16
+        }
+        RawTime::Minutes(minutes) => minutes,
+        RawTime::Time(time) => {
+            if time.date.is_some() {
+                return Err(D::Error::custom("Time should not have a date"));
             }
-        };
-        Ok(Time(Duration::minutes(minutes as i64)))
-    }
+            if time.offset.is_some() {
+                return Err(D::Error::custom("Time should not have an offset"));
+            }
+            let Some(time) = time.time else {
+                return Err(D::Error::custom("Time must contain a time"));
+            };
+            if time.second != 0 || time.nanosecond != 0 {
+                return Err(D::Error::custom("Time must contain whole minutes"));
+            }
+            time.hour as u16 * 60 + time.minute as u16
+        }
+    };
+    Ok(minutes)
 }
 
 impl From<Time<NaiveTime>> for Minutes {
@@ -188,6 +227,132 @@ impl From<Time<Duration>> for Minutes {
     }
 }
 
+/// The number of weeks between occurrences, counted from the start date.
+#[derive(Clone, Copy)]
+pub struct Every(pub u8);
+
+impl<'de> Deserialize<'de> for Every {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawEvery<'a> {
+            #[serde(borrow)]
+            String(Cow<'a, str>),
+            Weeks(u8),
+        }
+
+        let weeks = match RawEvery::deserialize(deserializer)? {
+            RawEvery::String(v) => match v.trim().split_once(' ') {
+                Some((weeks, "week" | "weeks")) => weeks.parse().map_err(D::Error::custom)?,
+                _ if v.trim() == "week" => 1,
+                _ => {
+                    return Err(D::Error::custom(
+                        "Expected a number of weeks like \"2 weeks\"",
+                    ))
+                }
+            },
+            RawEvery::Weeks(weeks) => weeks,
+        };
+        if weeks == 0 {
+            return Err(D::Error::custom("Events must repeat at least every week"));
+        }
+        Ok(Every(weeks))
+    }
+}
+
+/// A week of the month. Positive weeks count from the start of the month and negative weeks count
+/// from the end, so `-1` is the last seven days of the month.
+#[derive(Clone, Copy, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Week(pub i8);
+
+impl Week {
+    pub fn is_valid(self) -> bool {
+        matches!(self.0, 1..=5 | -5..=-1)
+    }
+
+    pub fn contains(self, date: NaiveDate) -> bool {
+        let same_month = |weeks: i64| (date + Duration::weeks(weeks)).month() == date.month();
+        let week = i64::from(self.0);
+        if week > 0 {
+            same_month(1 - week) && !same_month(-week)
+        } else {
+            same_month(-week - 1) && !same_month(-week)
+        }
+    }
+}
+
+/// Moves a single occurrence to a different date or time.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reschedule<'a> {
+    pub date: Spanned<NaiveDate>,
+    pub to: Option<Spanned<NaiveDate>>,
+    pub start: Option<Time<NaiveTime>>,
+    pub duration: Option<Time<Duration>>,
+    #[serde(borrow)]
+    pub description: Option<Cow<'a, str>>,
+}
+
+/// A date of a special event, optionally with its own times.
+pub struct SpecialDate {
+    pub date: NaiveDate,
+    pub start: Option<Time<NaiveTime>>,
+    pub duration: Option<Time<Duration>>,
+}
+
+impl<'de> Deserialize<'de> for SpecialDate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Table {
+            date: NaiveDate,
+            start: Option<Time<NaiveTime>>,
+            duration: Option<Time<Duration>>,
+        }
+
+        struct SpecialDateVisitor;
+        impl<'de> Visitor<'de> for SpecialDateVisitor {
+            type Value = SpecialDate;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a date or a table with a date")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(SpecialDate {
+                    date: v.parse().map_err(E::custom)?,
+                    start: None,
+                    duration: None,
+                })
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let table = Table::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(SpecialDate {
+                    date: table.date,
+                    start: table.start,
+                    duration: table.duration,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(SpecialDateVisitor)
+    }
+}
+
 #[derive(Clone)]
 pub enum DateSet {
     All(bool),
@@ -252,6 +417,127 @@ pub struct Meta<'a> {
     pub link: Option<Cow<'a, str>>,
     #[serde(borrow, default)]
     pub languages: HashMap<Language, MetaLanguage<'a>>,
+    #[serde(borrow, default)]
+    pub lints: HashMap<Spanned<Cow<'a, str>>, Level>,
+    #[serde(borrow, default)]
+    pub tags: BTreeMap<Cow<'a, str>, Tag<'a>>,
+    #[serde(default)]
+    pub posters: PosterOptions,
+}
+
+/// How poster images are processed before they're saved in the output.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PosterOptions {
+    /// Posters that are wider or taller than this are scaled down.
+    #[serde(default)]
+    pub max_size: PosterSize,
+    /// If this is set, every poster is converted to this format. Otherwise, posters keep their
+    /// format, except WebP which becomes PNG. Metadata like EXIF is always removed.
+    pub format: Option<PosterFormat>,
+    /// The quality of JPEG posters, from 1 to 100.
+    #[serde(default)]
+    pub quality: Quality,
+    /// Also packs the posters into a few atlas images, so the calendar script can load them with
+    /// fewer requests.
+    #[serde(default)]
+    pub atlas: bool,
+}
+
+/// The largest width and height of a poster.
+#[derive(Clone, Copy)]
+pub struct PosterSize(pub u32);
+
+/// VRChat can't load images larger than this.
+pub const MAX_POSTER_SIZE: u32 = 2048;
+
+impl Default for PosterSize {
+    fn default() -> Self {
+        PosterSize(MAX_POSTER_SIZE)
+    }
+}
+
+impl<'de> Deserialize<'de> for PosterSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let size = u32::deserialize(deserializer)?;
+        if !(1..=MAX_POSTER_SIZE).contains(&size) {
+            return Err(D::Error::custom(format!(
+                "The size must be between 1 and {MAX_POSTER_SIZE}"
+            )));
+        }
+        Ok(PosterSize(size))
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PosterFormat {
+    Png,
+    Jpeg,
+}
+
+#[derive(Clone, Copy)]
+pub struct Quality(pub u8);
+
+impl Default for Quality {
+    fn default() -> Self {
+        Quality(85)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quality {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let quality = u8::deserialize(deserializer)?;
+        if !(1..=100).contains(&quality) {
+            return Err(D::Error::custom("The quality must be between 1 and 100"));
+        }
+        Ok(Quality(quality))
+    }
+}
+
+/// A tag that events can use, defined in the meta file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tag<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub color: Option<Color<'a>>,
+    #[serde(borrow, default)]
+    pub languages: HashMap<Language, TagLanguage<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TagLanguage<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+}
+
+/// A CSS hex color like `#c0768c`.
+pub struct Color<'a>(pub Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Color<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let color = Cow::<'a, str>::deserialize(deserializer)?;
+        match color.strip_prefix('#') {
+            Some(hex)
+                if matches!(hex.len(), 3 | 6) && hex.bytes().all(|b| b.is_ascii_hexdigit()) =>
+            {
+                Ok(Color(color))
+            }
+            _ => Err(D::Error::custom("Expected a hex color like \"#c0768c\"")),
+        }
+    }
 }
 
 #[derive(Deserialize)]
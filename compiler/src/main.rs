@@ -1,30 +1,26 @@
 use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
     ffi::OsStr,
     fmt,
     fs::{self, File},
     hash::{Hash, Hasher},
-    io::{self, BufReader, BufWriter, Cursor, Seek, SeekFrom, Write},
+    io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    ops::Range,
     path::{Path, PathBuf},
     process::ExitCode,
     str::FromStr,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
+    sync::Arc,
 };
 
-use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
 use chrono_tz::Tz;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use error::StateParseError;
 use flate2::bufread::GzDecoder;
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageOutputFormat};
 use iso639_enum::IsoCompat;
-use miette::{
-    miette, Context, Diagnostic, IntoDiagnostic, MietteHandler, NamedSource, Report, ReportHandler,
-    Result, Severity,
-};
+use miette::{miette, Context, IntoDiagnostic, LabeledSpan, NamedSource, Report, Result};
 
 use output::{Hashtag, Zone};
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
@@ -35,11 +31,22 @@ use tar::Archive;
 use tempfile::NamedTempFile;
 
 use crate::error::{
-    CanceledOutOfRange, ConfirmedOutOfRange, ImageTooLarge, MissingTimeZone, MultiplePosters,
+    CanceledOutOfRange, ConfirmedAndCanceled, ConfirmedOutOfRange, DatesConflict, DuplicateDate,
+    DuplicateName, DuplicateWeek, DurationOverlap, EndBeforeStart, EveryWithoutStartDate,
+    ImageTooLarge, InvalidWeek, MissingBase, MissingTimeZone, MultiplePosters, NoDates, NoWeeks,
+    RescheduleOutOfRange, RescheduleOverlap, UnknownLint, UnknownMetaLint, UnknownTag,
 };
 
+mod atlas;
+mod conflicts;
+mod diagnostics;
 mod error;
+mod extends;
+mod gc;
+mod ics;
+mod import;
 mod input;
+mod occurrences;
 mod output;
 mod state;
 mod time;
@@ -47,92 +54,172 @@ mod time;
 const WEB: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/web.tgz"));
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    input: PathBuf,
-    output: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// How problems are reported.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    diagnostics_format: diagnostics::Format,
+    /// Report warnings with this code (or `warnings` for all warnings) as errors.
+    #[arg(long, global = true, value_name = "CODE")]
+    deny: Vec<String>,
+    /// Report warnings with this code (or `warnings` for all warnings) as warnings.
+    #[arg(long, global = true, value_name = "CODE")]
+    warn: Vec<String>,
+    /// Ignore warnings with this code (or `warnings` for all warnings).
+    #[arg(long, global = true, value_name = "CODE")]
+    allow: Vec<String>,
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+    #[arg(required = true)]
+    output: Option<PathBuf>,
     web: Option<PathBuf>,
+    /// Also save occurrences.json with every occurrence in this many weeks.
+    #[arg(long, value_name = "WEEKS")]
+    occurrences: Option<u32>,
 }
 
-fn main() -> ExitCode {
-    let args = Args::parse();
-
-    let errors = Arc::new(AtomicUsize::new(0));
-    miette::set_hook({
-        let errors = errors.clone();
-        Box::new(move |_| {
-            Box::new(Handler {
-                inner: MietteHandler::new(),
-                errors: errors.clone(),
-            })
-        })
-    })
-    .unwrap();
+#[derive(Subcommand)]
+enum Command {
+    /// Validate the input directory without writing any output.
+    Check { input: PathBuf },
+    /// Convert the events in an iCalendar file to event files.
+    ImportIcs {
+        /// The iCalendar file to read.
+        file: PathBuf,
+        /// The directory to save the event files in.
+        output: PathBuf,
+    },
+    /// Create or update event files from the rows of a CSV file.
+    ImportCsv {
+        /// The CSV file to read.
+        file: PathBuf,
+        /// The input directory with the event files.
+        input: PathBuf,
+    },
+    /// Remove posters that haven't been used for a while from the output directory.
+    Gc {
+        /// The output directory.
+        output: PathBuf,
+        /// Remove posters that haven't been used for this long, like `90d` or `12w`.
+        #[arg(long, value_name = "AGE", default_value = "90d", value_parser = gc::parse_age)]
+        older_than: chrono::Duration,
+        /// List what would be removed without removing anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
-    if !args.output.exists() {
-        if let Err(err) = fs::create_dir_all(&args.output)
-            .into_diagnostic()
-            .wrap_err("Could not create output directory")
+/// Where the compiled calendar is saved.
+struct Destination<'a> {
+    output: &'a Path,
+    web: Option<&'a Path>,
+    /// The number of weeks to list in occurrences.json, if it should be saved.
+    occurrences: Option<u32>,
+}
+
+fn main() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // When the same code is given more than once, the last one on the command line is used.
+    let mut levels = Vec::new();
+    for (id, level) in [
+        ("deny", diagnostics::Level::Deny),
+        ("warn", diagnostics::Level::Warn),
+        ("allow", diagnostics::Level::Allow),
+    ] {
+        if let (Some(indices), Some(codes)) =
+            (matches.indices_of(id), matches.get_many::<String>(id))
         {
-            eprintln!("{err:?}");
-            return ExitCode::FAILURE;
+            levels.extend(indices.zip(codes).map(|(index, code)| (index, code, level)));
         }
     }
-
-    let now = Utc::now();
-
-    let mut state = match load_state(&args.output) {
-        Ok(state) => state,
-        Err(error) => {
-            eprintln!("{error:?}");
-            return ExitCode::FAILURE;
+    levels.sort_unstable_by_key(|&(index, _, _)| index);
+    let mut lints = diagnostics::Lints::default();
+    for &(_, code, level) in &levels {
+        lints.set(code, level);
+    }
+    diagnostics::init(args.diagnostics_format, lints);
+    for (_, code, _) in levels {
+        if !diagnostics::is_known(code) {
+            diagnostics::report(UnknownLint { code: code.clone() });
         }
+    }
+
+    let code = match &args.command {
+        Some(Command::Check { input }) => compile(input, None),
+        Some(Command::ImportIcs { file, output }) => import::ics::import(file, output),
+        Some(Command::ImportCsv { file, input }) => import::csv::import(file, input),
+        Some(Command::Gc {
+            output,
+            older_than,
+            dry_run,
+        }) => gc::gc(output, *older_than, *dry_run),
+        None => compile(
+            args.input.as_deref().unwrap(),
+            args.output.as_deref().map(|output| Destination {
+                output,
+                web: args.web.as_deref(),
+                occurrences: args.occurrences,
+            }),
+        ),
     };
-    let mut posters = Posters::load(args.output.join("posters"), &state, now);
+    diagnostics::finish();
+    code
+}
 
-    let mut files = BTreeSet::<PathBuf>::new();
-    match fs::read_dir(&args.input)
-        .into_diagnostic()
-        .wrap_err("Collecting input failed.")
-    {
-        Ok(dir) => {
-            for file in dir {
-                match file.into_diagnostic().wrap_err("Collecting input failed.") {
-                    Ok(file) => {
-                        files.insert(file.path());
-                    }
-                    Err(error) => {
-                        eprintln!("{error:?}");
-                    }
-                }
+/// Compiles the events in `input`.
+///
+/// If there is no destination, the input is only validated and nothing is written.
+fn compile(input: &Path, destination: Option<Destination>) -> ExitCode {
+    if let Some(destination) = &destination {
+        if !destination.output.exists() {
+            if let Err(err) = fs::create_dir_all(destination.output)
+                .into_diagnostic()
+                .wrap_err("Could not create output directory")
+            {
+                diagnostics::report(err);
+                return ExitCode::FAILURE;
             }
         }
-        Err(error) => {
-            eprintln!("{error:?}");
-        }
     }
 
-    let meta_file = if let Some(meta_file) = files
-        .iter()
-        .find(|f| f.file_name() == Some(OsStr::new("meta.toml")))
-    {
-        match fs::read_to_string(meta_file)
-            .into_diagnostic()
-            .wrap_err_with(|| format!("Reading {} failed.", meta_file.display()))
-        {
-            Ok(content) => Arc::new(content),
+    let now = Utc::now();
+
+    let mut state = match &destination {
+        Some(destination) => match load_state(destination.output) {
+            Ok(state) => state,
             Err(error) => {
-                eprintln!("{error:?}");
+                diagnostics::report(error);
                 return ExitCode::FAILURE;
             }
-        }
-    } else {
-        eprintln!("{:?}", miette!("meta.toml not found."));
+        },
+        None => State::default(),
+    };
+    let mut files = BTreeSet::<PathBuf>::new();
+    collect_files(input, &mut files);
+
+    let meta_path = input.join("meta.toml");
+    if !files.contains(&meta_path) {
+        diagnostics::report(miette!("meta.toml not found."));
         return ExitCode::FAILURE;
+    }
+    let meta_file = match fs::read_to_string(&meta_path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Reading {} failed.", meta_path.display()))
+    {
+        Ok(content) => Arc::new(content),
+        Err(error) => {
+            diagnostics::report(error);
+            return ExitCode::FAILURE;
+        }
     };
 
     let meta = match input::Meta::deserialize(toml::Deserializer::new(&meta_file))
         .map_err(|error| error::EventParseError {
-            src: NamedSource::new("meta.toml", meta_file.clone()),
+            src: NamedSource::new(meta_path.to_string_lossy(), meta_file.clone()),
             location: error.span().map(|s| s.into()),
             error,
         })
@@ -140,11 +227,35 @@ fn main() -> ExitCode {
     {
         Ok(meta) => meta,
         Err(error) => {
-            eprintln!("{error:?}");
+            diagnostics::report(error);
             return ExitCode::FAILURE;
         }
     };
 
+    let mut lints = diagnostics::Lints::default();
+    for (code, &level) in &meta.lints {
+        lints.set(code.get_ref().as_ref(), level);
+    }
+    diagnostics::set_meta_lints(lints);
+    let mut codes: Vec<_> = meta.lints.keys().collect();
+    codes.sort_unstable_by_key(|code| code.span().start);
+    for code in codes {
+        if !diagnostics::is_known(code.as_ref()) {
+            diagnostics::report(UnknownMetaLint {
+                code: code.as_ref().to_string(),
+                src: NamedSource::new(meta_path.to_string_lossy(), meta_file.clone()),
+                location: code.span().into(),
+            });
+        }
+    }
+
+    let mut posters = Posters::load(
+        destination.as_ref().map(|d| d.output),
+        &state,
+        meta.posters,
+        now,
+    );
+
     let output_meta = output::Meta {
         title: &meta.title,
         description: meta.description.as_deref(),
@@ -164,12 +275,38 @@ fn main() -> ExitCode {
                 )
             })
             .collect(),
+        tags: meta
+            .tags
+            .iter()
+            .map(|(id, tag)| {
+                (
+                    id.as_ref(),
+                    output::Tag {
+                        name: &tag.name,
+                        color: tag.color.as_ref().map(|c| c.0.as_ref()),
+                        languages: tag
+                            .languages
+                            .iter()
+                            .map(|(&id, language)| {
+                                (
+                                    id,
+                                    output::TagLanguage {
+                                        name: &language.name,
+                                    },
+                                )
+                            })
+                            .collect(),
+                    },
+                )
+            })
+            .collect(),
     };
 
     let mut event_files = Vec::new();
-    for file in files.iter().filter(|f| {
-        f.file_name() != Some(OsStr::new("meta.toml")) && f.extension() == Some(OsStr::new("toml"))
-    }) {
+    for file in files
+        .iter()
+        .filter(|f| **f != meta_path && f.extension() == Some(OsStr::new("toml")))
+    {
         match fs::read_to_string(file)
             .into_diagnostic()
             .wrap_err_with(|| format!("Reading {} failed.", file.display()))
@@ -181,25 +318,68 @@ fn main() -> ExitCode {
                 });
             }
             Err(error) => {
-                eprintln!("{error:?}");
+                diagnostics::report(error);
             }
         };
     }
 
+    let base_paths: BTreeSet<_> = event_files.iter().filter_map(extends::base_path).collect();
+    // Base files that can't be read are reported by the events that extend them.
+    let base_files: Vec<_> = base_paths
+        .iter()
+        .filter_map(|path| {
+            Some(EventFile {
+                path,
+                content: Arc::new(fs::read_to_string(path).ok()?),
+            })
+        })
+        .collect();
+    let mut bases = HashMap::with_capacity(base_files.len());
+    for file in base_files.iter() {
+        match input::EventInfo::deserialize(toml::Deserializer::new(&file.content))
+            .map_err(|error| error::EventParseError::new(error, file))
+            .wrap_err_with(|| format!("Parsing {} failed.", file.path.display()))
+        {
+            Ok(info) => {
+                bases.insert(file.path, (file, info));
+            }
+            Err(error) => {
+                diagnostics::report(error);
+            }
+        }
+    }
+
     let mut input_events = Vec::with_capacity(event_files.len());
     for file in event_files.iter() {
         match input::Event::deserialize(toml::Deserializer::new(&file.content))
             .map_err(|error| error::EventParseError::new(error, file))
             .wrap_err_with(|| format!("Parsing {} failed.", file.path.display()))
         {
-            Ok(input) => {
+            Ok(mut input) => {
+                let mut base_source = None;
+                if let Some(base) = &input.extends {
+                    let path = extends::resolve(file, base.as_ref());
+                    if let Some((base_file, base_info)) = bases.get(path.as_path()) {
+                        extends::check_overrides(file, base_file);
+                        extends::merge(&mut input.info, base_info);
+                        base_source = Some(*base_file);
+                    } else if !base_files.iter().any(|f| f.path == path) {
+                        diagnostics::report(MissingBase {
+                            path,
+                            src: file.into(),
+                            location: base.span().into(),
+                        });
+                        continue;
+                    }
+                }
                 input_events.push(Event {
                     source: file,
+                    base: base_source,
                     event: input,
                 });
             }
             Err(error) => {
-                eprintln!("{error:?}");
+                diagnostics::report(error);
             }
         }
     }
@@ -207,29 +387,68 @@ fn main() -> ExitCode {
     let zones = time::collect_zones(now);
 
     let mut output_events = Vec::with_capacity(input_events.len());
+    let mut prepared_events = Vec::with_capacity(input_events.len());
+    let mut names = HashMap::with_capacity(input_events.len());
     for event in input_events.iter() {
-        match prepare_event(event, &files, &zones, now, &mut posters).wrap_err_with(|| {
-            format!(
-                "File {} could not be processed.",
-                event.source.path.display(),
-            )
-        }) {
-            Ok(event) => output_events.push(event),
-            Err(error) => eprintln!("{error:?}"),
+        match prepare_event(event, input, &files, &zones, &meta.tags, now, &mut posters)
+            .wrap_err_with(|| {
+                format!(
+                    "File {} could not be processed.",
+                    event.source.path.display(),
+                )
+            }) {
+            Ok(output) => {
+                if let Some(first) = names.insert(output.name.clone(), event.source.path) {
+                    diagnostics::report(DuplicateName {
+                        path: event.source.path.to_owned(),
+                        first: first.to_owned(),
+                    });
+                }
+                output_events.push(output);
+                prepared_events.push(event);
+            }
+            Err(error) => diagnostics::report(error),
+        }
+    }
+
+    let atlas = meta
+        .posters
+        .atlas
+        .then(|| atlas::Atlas::pack(&posters.used));
+    if let Some(atlas) = &atlas {
+        for poster in output_events.iter_mut().flat_map(|e| e.posters_mut()) {
+            poster.atlas = atlas.rect(poster.number);
         }
     }
 
-    if errors.load(Ordering::SeqCst) == 0 {
+    let events: Vec<_> = prepared_events
+        .iter()
+        .copied()
+        .zip(output_events.iter())
+        .collect();
+    conflicts::check(&events, now);
+
+    if diagnostics::errors() != 0 {
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(destination) = &destination {
         posters.save(&mut state);
-        if let Err(e) = safely_save(&args.output, "state.json", |mut t| {
-            serde_json::to_writer_pretty(&mut t, &state).into_diagnostic()?;
-            t.write_all(b"\n").into_diagnostic()
-        }) {
-            eprintln!("{e:?}");
+        if let Err(e) = save_state(destination.output, &state) {
+            diagnostics::report(e);
+            return ExitCode::FAILURE;
+        }
+
+        let result = match &atlas {
+            Some(atlas) => atlas.save(destination.output, &meta.posters),
+            None => atlas::remove(destination.output),
+        };
+        if let Err(e) = result {
+            diagnostics::report(e);
             return ExitCode::FAILURE;
         }
 
-        if let Err(e) = safely_save(&args.output, "data.json", |mut t| {
+        if let Err(e) = safely_save(destination.output, "data.json", |mut t| {
             serde_json::to_writer(
                 &mut t,
                 &output::Data {
@@ -241,26 +460,90 @@ fn main() -> ExitCode {
             .into_diagnostic()?;
             t.write_all(b"\n").into_diagnostic()
         }) {
-            eprintln!("{e:?}");
+            diagnostics::report(e);
+            return ExitCode::FAILURE;
+        }
+
+        if let Err(e) = ics::save(destination.output, &output_meta, &events, &zones, now) {
+            diagnostics::report(e);
+            return ExitCode::FAILURE;
+        }
+
+        let occurrences = match destination.occurrences {
+            Some(weeks) => occurrences::save(destination.output, &events, now, weeks),
+            None => remove_occurrences(destination.output),
+        };
+        if let Err(e) = occurrences {
+            diagnostics::report(e);
             return ExitCode::FAILURE;
         }
 
-        if let Some(web) = &args.web {
-            if let Err(e) = deploy_web(&args, web)
+        if let Some(web) = destination.web {
+            if let Err(e) = deploy_web(destination.output, web)
                 .wrap_err_with(|| format!("Deploying web files to {} failed.", web.display()))
             {
-                eprintln!("{e:?}");
+                diagnostics::report(e);
                 return ExitCode::FAILURE;
             }
         }
+    }
 
-        ExitCode::SUCCESS
-    } else {
-        ExitCode::FAILURE
+    ExitCode::SUCCESS
+}
+
+/// Removes occurrences.json if it was saved by an earlier build, so it doesn't go stale.
+fn remove_occurrences(output: &Path) -> miette::Result<()> {
+    match fs::remove_file(output.join("occurrences.json")) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e)
+            .into_diagnostic()
+            .wrap_err("Deleting occurrences.json failed."),
+        _ => Ok(()),
+    }
+}
+
+/// Collects the files in the input directory and its subdirectories. Directories starting with `.`
+/// or `_` are skipped so they can be used for base files and other things that aren't events.
+fn collect_files(directory: &Path, files: &mut BTreeSet<PathBuf>) {
+    let dir = match fs::read_dir(directory)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Collecting input from {} failed.", directory.display()))
+    {
+        Ok(dir) => dir,
+        Err(error) => {
+            diagnostics::report(error);
+            return;
+        }
+    };
+    for entry in dir {
+        let entry = match entry
+            .and_then(|entry| Ok((entry.path(), entry.file_type()?)))
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Collecting input from {} failed.", directory.display()))
+        {
+            Ok(entry) => entry,
+            Err(error) => {
+                diagnostics::report(error);
+                continue;
+            }
+        };
+        match entry {
+            (path, file_type) if file_type.is_dir() => {
+                let skipped = path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .is_some_and(|name| name.starts_with(['.', '_']));
+                if !skipped {
+                    collect_files(&path, files);
+                }
+            }
+            (path, _) => {
+                files.insert(path);
+            }
+        }
     }
 }
 
-fn deploy_web(args: &Args, web: &Path) -> miette::Result<()> {
+fn deploy_web(output: &Path, web: &Path) -> miette::Result<()> {
     match fs::remove_dir_all(web).into_diagnostic() {
         Ok(_) => {}
         Err(_) if !web.exists() => {}
@@ -278,26 +561,53 @@ fn deploy_web(args: &Args, web: &Path) -> miette::Result<()> {
         .into_diagnostic()
         .wrap_err("Extracting web assets failed.")?;
 
-    fs::copy(args.output.join("data.json"), web.join("data.json"))
+    fs::copy(output.join("data.json"), web.join("data.json"))
         .into_diagnostic()
         .wrap_err("Copying data.json failed.")?;
 
-    let web_posters = web.join("posters");
-    fs::create_dir(&web_posters)
+    for file in fs::read_dir(output)
         .into_diagnostic()
-        .wrap_err("Creating web poster directory failed.")?;
-    for poster in fs::read_dir(args.output.join("posters"))
+        .wrap_err("Reading the output directory failed.")?
+    {
+        let file = file
+            .into_diagnostic()
+            .wrap_err("Reading the output directory failed.")?;
+        let name = file.file_name();
+        if name.to_str().is_some_and(|n| {
+            n == "occurrences.json" || n.starts_with("calendar.") && n.ends_with(".ics")
+        }) {
+            fs::copy(file.path(), web.join(&name))
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Copying {} failed.", file.path().display()))?;
+        }
+    }
+
+    copy_directory(&output.join("ics"), &web.join("ics"))?;
+    copy_directory(&output.join("posters"), &web.join("posters"))?;
+    copy_directory(&output.join("thumbnails"), &web.join("thumbnails"))?;
+    let atlas = output.join("atlas");
+    if atlas.exists() {
+        copy_directory(&atlas, &web.join("atlas"))?;
+    }
+
+    Ok(())
+}
+
+fn copy_directory(from: &Path, to: &Path) -> miette::Result<()> {
+    fs::create_dir(to)
         .into_diagnostic()
-        .wrap_err("Reading posters failed.")?
+        .wrap_err_with(|| format!("Creating {} failed.", to.display()))?;
+    for file in fs::read_dir(from)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Reading {} failed.", from.display()))?
     {
-        let poster = poster
+        let file = file
             .into_diagnostic()
-            .wrap_err("Reading posters failed.")?;
-        fs::copy(poster.path(), web_posters.join(poster.file_name()))
+            .wrap_err_with(|| format!("Reading {} failed.", from.display()))?;
+        fs::copy(file.path(), to.join(file.file_name()))
             .into_diagnostic()
-            .wrap_err_with(|| format!("Copying poster {} failed.", poster.path().display()))?;
+            .wrap_err_with(|| format!("Copying {} failed.", file.path().display()))?;
     }
-
     Ok(())
 }
 
@@ -315,12 +625,22 @@ fn load_state(output_path: &Path) -> miette::Result<State> {
                 .wrap_err_with(|| format!("Could not read {}", state_path.display()))
         }
     };
-    match serde_json::from_slice(&state) {
-        Ok(state) => Ok(state),
-        Err(e) => Err(StateParseError::new(e, &output_path.to_string_lossy(), state).into()),
+    match serde_json::from_slice::<State>(&state) {
+        Ok(mut state) => {
+            state.migrate();
+            Ok(state)
+        }
+        Err(e) => Err(StateParseError::new(e, &state_path.to_string_lossy(), state).into()),
     }
 }
 
+fn save_state(output_path: &Path, state: &State) -> miette::Result<()> {
+    safely_save(output_path, "state.json", |mut t| {
+        serde_json::to_writer_pretty(&mut t, state).into_diagnostic()?;
+        t.write_all(b"\n").into_diagnostic()
+    })
+}
+
 fn safely_save(
     output_path: &Path,
     name: &str,
@@ -342,25 +662,6 @@ fn safely_save(
         .wrap_err_with(|| format!("Could not save {}", save_path.display()))
 }
 
-struct Handler {
-    inner: MietteHandler,
-    errors: Arc<AtomicUsize>,
-}
-
-impl ReportHandler for Handler {
-    fn debug(
-        &self,
-        error: &(dyn Diagnostic),
-        f: &mut core::fmt::Formatter<'_>,
-    ) -> core::fmt::Result {
-        let severity = error.severity().unwrap_or(miette::Severity::Error);
-        if severity == Severity::Error {
-            self.errors.fetch_add(1, Ordering::SeqCst);
-        }
-        self.inner.debug(error, f)
-    }
-}
-
 pub struct EventFile<'a> {
     path: &'a Path,
     content: Arc<String>,
@@ -368,41 +669,81 @@ pub struct EventFile<'a> {
 
 pub struct Event<'a> {
     source: &'a EventFile<'a>,
+    /// The base file the event inherits details from.
+    base: Option<&'a EventFile<'a>>,
     event: input::Event<'a>,
 }
 
 impl<'a> Event<'a> {
-    pub fn get_time_for_day(
-        &self,
-        date: NaiveDate,
-        timezone: Tz,
-        force: bool,
-    ) -> Result<Option<DateTime<Tz>>> {
-        if let Some(start_date) = self.event.start_date {
-            if date < start_date {
+    pub fn get_time_for_day(&self, date: NaiveDate, timezone: Tz) -> Result<Option<DateTime<Tz>>> {
+        if let Some(dates) = &self.event.dates {
+            if !dates.as_ref().iter().any(|d| d.as_ref().date == date) {
                 return Ok(None);
             }
+            let (time, _) = self.get_schedule_for_day(date);
+            return Ok(date.and_time(time).and_local_timezone(timezone).earliest());
         }
-        if let Some(end_date) = self.event.end_date {
-            if end_date < date {
+        if let Some(start_date) = &self.event.start_date {
+            if date < *start_date.as_ref() {
                 return Ok(None);
             }
         }
-        let day = match date.weekday() {
-            chrono::Weekday::Mon => self.event.days.monday.as_ref(),
-            chrono::Weekday::Tue => self.event.days.tuesday.as_ref(),
-            chrono::Weekday::Wed => self.event.days.wednesday.as_ref(),
-            chrono::Weekday::Thu => self.event.days.thursday.as_ref(),
-            chrono::Weekday::Fri => self.event.days.friday.as_ref(),
-            chrono::Weekday::Sat => self.event.days.saturday.as_ref(),
-            chrono::Weekday::Sun => self.event.days.sunday.as_ref(),
-        };
-        if !force && day.is_none() {
-            return Ok(None);
+        if let Some(end_date) = &self.event.end_date {
+            if *end_date.as_ref() < date {
+                return Ok(None);
+            }
+        }
+        if let (Some(every), Some(start_date)) = (&self.event.every, &self.event.start_date) {
+            if (date - *start_date.as_ref()).num_days().div_euclid(7) % i64::from(every.as_ref().0)
+                != 0
+            {
+                return Ok(None);
+            }
+        }
+        if let Some(weeks) = &self.event.weeks {
+            if !weeks.as_ref().iter().any(|w| w.as_ref().contains(date)) {
+                return Ok(None);
+            }
+        }
+        if let Some(days) = &self.event.days {
+            if days.get(date.weekday()).is_none() {
+                return Ok(None);
+            }
         }
-        let time = day.and_then(|d| d.start).unwrap_or(self.event.start).0;
+        let (time, _) = self.get_schedule_for_day(date);
         Ok(date.and_time(time).and_local_timezone(timezone).earliest())
     }
+
+    /// Gets the start time and duration the event would have on a date, whether or not it is held
+    /// on that date.
+    pub fn get_schedule_for_day(&self, date: NaiveDate) -> (NaiveTime, chrono::Duration) {
+        let (start, duration) = if let Some(special) = self
+            .event
+            .dates
+            .as_ref()
+            .and_then(|dates| dates.as_ref().iter().find(|d| d.as_ref().date == date))
+        {
+            (special.as_ref().start, special.as_ref().duration)
+        } else {
+            return self.get_schedule_for_weekday(date.weekday());
+        };
+        (
+            start.unwrap_or(self.event.start).0,
+            duration.unwrap_or(*self.event.duration.as_ref()).0,
+        )
+    }
+
+    /// Gets the start time and duration the event would have on a weekday, ignoring special dates.
+    pub fn get_schedule_for_weekday(&self, weekday: Weekday) -> (NaiveTime, chrono::Duration) {
+        let (start, duration) = match self.event.days.as_ref().and_then(|days| days.get(weekday)) {
+            Some(day) => (day.start, day.duration),
+            None => (None, None),
+        };
+        (
+            start.unwrap_or(self.event.start).0,
+            duration.unwrap_or(*self.event.duration.as_ref()).0,
+        )
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -415,6 +756,13 @@ pub enum Platform {
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub struct Language(iso639_enum::Language);
 
+impl Language {
+    /// Gets the ISO 639-1 code, or the ISO 639-3 code if there isn't one.
+    pub fn code(&self) -> &str {
+        self.0.iso639_1().unwrap_or_else(|| self.0.iso639_3())
+    }
+}
+
 impl<'de> Deserialize<'de> for Language {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -463,7 +811,7 @@ impl Serialize for Language {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.0.iso639_1().unwrap())
+        serializer.serialize_str(self.code())
     }
 }
 
@@ -475,8 +823,10 @@ impl Hash for Language {
 
 fn prepare_event<'a, 'b>(
     event: &'a Event<'a>,
+    input: &'b Path,
     files: &'b BTreeSet<PathBuf>,
     zones: &'b BTreeMap<String, Zone>,
+    tags: &'b BTreeMap<Cow<str>, input::Tag>,
     now: DateTime<Utc>,
     posters: &'b mut Posters,
 ) -> Result<output::Event<'a>> {
@@ -486,6 +836,76 @@ fn prepare_event<'a, 'b>(
     let Ok(tz) = Tz::from_str(event.event.timezone.as_ref().as_ref()) else {
         return Err(MissingTimeZone::new(event).into());
     };
+    if let (Some(start_date), Some(end_date)) = (&event.event.start_date, &event.event.end_date) {
+        if end_date.as_ref() < start_date.as_ref() {
+            return Err(EndBeforeStart {
+                src: event.source.into(),
+                start: start_date.span().into(),
+                end: end_date.span().into(),
+            }
+            .into());
+        }
+    }
+    if let (Some(every), None) = (&event.event.every, &event.event.start_date) {
+        return Err(EveryWithoutStartDate {
+            src: event.source.into(),
+            location: every.span().into(),
+        }
+        .into());
+    }
+    if let Some(dates) = &event.event.dates {
+        let conflict = [
+            ("days", event.event.days.is_some()),
+            ("a start date", event.event.start_date.is_some()),
+            ("an end date", event.event.end_date.is_some()),
+            ("every", event.event.every.is_some()),
+            ("weeks", event.event.weeks.is_some()),
+            (
+                "confirmed dates",
+                !matches!(event.event.confirmed, input::DateSet::All(true)),
+            ),
+            (
+                "canceled dates",
+                !matches!(event.event.canceled, input::DateSet::All(false)),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(field, present)| present.then_some(field));
+        if let Some(field) = conflict {
+            return Err(DatesConflict {
+                field,
+                src: event.source.into(),
+                location: dates.span().into(),
+            }
+            .into());
+        }
+        if dates.as_ref().is_empty() {
+            return Err(NoDates {
+                src: event.source.into(),
+                location: dates.span().into(),
+            }
+            .into());
+        }
+    } else {
+        check_overlap(event);
+    }
+    if let Some(weeks) = &event.event.weeks {
+        if weeks.as_ref().is_empty() {
+            return Err(NoWeeks {
+                src: event.source.into(),
+                location: weeks.span().into(),
+            }
+            .into());
+        }
+        if let Some(week) = weeks.as_ref().iter().find(|w| !w.as_ref().is_valid()) {
+            return Err(InvalidWeek {
+                week: week.as_ref().0,
+                src: event.source.into(),
+                location: week.span().into(),
+            }
+            .into());
+        }
+    }
 
     let poster = event
         .event
@@ -495,7 +915,7 @@ fn prepare_event<'a, 'b>(
         .map(Path::new)
         .map(Cow::Borrowed)
         .or_else(|| guess_poster(event, files).map(Cow::Owned));
-    let poster = poster.and_then(try_load_poster);
+    let poster = poster.and_then(|p| posters.try_load(p));
 
     let name = event
         .event
@@ -517,69 +937,186 @@ fn prepare_event<'a, 'b>(
         );
     }
 
-    let confirmed = match &event.event.confirmed {
-        input::DateSet::All(b) => output::DateSet::All(*b),
-        input::DateSet::Dates(confirmed) => {
-            let mut future = Vec::with_capacity(confirmed.len());
-            for date in confirmed {
-                let Some(time) = event.get_time_for_day(*date.as_ref(), tz, true)? else {
-                    eprintln!(
-                        "{:?}",
-                        Report::new(ConfirmedOutOfRange {
-                            date: *date.as_ref(),
-                            src: event.source.into(),
-                            location: date.span().into(),
-                        }),
-                    );
+    let confirmed = convert_date_set(event, &event.event.confirmed, tz, now, |date, span| {
+        ConfirmedOutOfRange {
+            date,
+            src: event.source.into(),
+            location: span.into(),
+        }
+        .into()
+    })?;
+    let canceled = convert_date_set(event, &event.event.canceled, tz, now, |date, span| {
+        CanceledOutOfRange {
+            date,
+            src: event.source.into(),
+            location: span.into(),
+        }
+        .into()
+    })?;
+    if let (input::DateSet::Dates(confirmed), input::DateSet::Dates(canceled)) =
+        (&event.event.confirmed, &event.event.canceled)
+    {
+        for date in canceled {
+            if let Some(first) = confirmed.iter().find(|c| c.as_ref() == date.as_ref()) {
+                diagnostics::report(ConfirmedAndCanceled {
+                    date: *date.as_ref(),
+                    src: event.source.into(),
+                    confirmed: first.span().into(),
+                    canceled: date.span().into(),
+                });
+            }
+        }
+    }
+
+    let dates = match &event.event.dates {
+        Some(dates) => {
+            let mut seen = HashMap::with_capacity(dates.as_ref().len());
+            let mut future = Vec::with_capacity(dates.as_ref().len());
+            for special in dates.as_ref() {
+                let date = special.as_ref().date;
+                if let Some(first) = seen.insert(date, special.span()) {
+                    diagnostics::report(DuplicateDate {
+                        date,
+                        src: event.source.into(),
+                        location: special.span().into(),
+                        first: first.into(),
+                    });
                     continue;
+                }
+                let Some(time) = event.get_time_for_day(date, tz)? else {
+                    return Err(nonexistent_time(event, special.span(), date, tz));
                 };
                 if now < time {
-                    future.push(*date.as_ref());
+                    let (start, duration) = event.get_schedule_for_day(date);
+                    future.push(output::SpecialDate {
+                        date,
+                        start: special.as_ref().start.map(Into::into),
+                        duration: special.as_ref().duration.map(Into::into),
+                        end_day: end_day(start, duration),
+                    });
                 }
             }
-            if future.is_empty() {
-                output::DateSet::All(false)
-            } else {
-                output::DateSet::Dates(future)
-            }
+            Some(future)
         }
+        None => None,
     };
 
-    let canceled = match &event.event.canceled {
-        input::DateSet::All(b) => output::DateSet::All(*b),
-        input::DateSet::Dates(canceled) => {
-            let mut future = Vec::with_capacity(canceled.len());
-            for date in canceled {
-                let Some(time) = event.get_time_for_day(*date.as_ref(), tz, false)? else {
-                    eprintln!(
-                        "{:?}",
-                        Report::new(CanceledOutOfRange {
-                            date: *date.as_ref(),
-                            src: event.source.into(),
-                            location: date.span().into(),
-                        }),
-                    );
-                    continue;
-                };
-                if now < time {
-                    future.push(*date.as_ref());
-                }
+    let weeks = event.event.weeks.as_ref().map(|weeks| {
+        let mut seen = HashMap::with_capacity(weeks.as_ref().len());
+        let mut unique = Vec::with_capacity(weeks.as_ref().len());
+        for week in weeks.as_ref() {
+            if let Some(first) = seen.insert(*week.as_ref(), week.span()) {
+                diagnostics::report(DuplicateWeek {
+                    week: week.as_ref().0,
+                    src: event.source.into(),
+                    location: week.span().into(),
+                    first: first.into(),
+                });
+                continue;
             }
-            if future.is_empty() {
-                output::DateSet::All(false)
-            } else {
-                output::DateSet::Dates(future)
+            unique.push(*week.as_ref());
+        }
+        unique
+    });
+
+    let mut reschedule = Vec::with_capacity(event.event.reschedule.len());
+    let mut seen = HashMap::with_capacity(event.event.reschedule.len());
+    for moved in &event.event.reschedule {
+        let original = &moved.as_ref().date;
+        let date = *original.as_ref();
+        if let Some(first) = seen.insert(date, original.span()) {
+            diagnostics::report(DuplicateDate {
+                date,
+                src: event.source.into(),
+                location: original.span().into(),
+                first: first.into(),
+            });
+            continue;
+        }
+        let Some(original_time) = event.get_time_for_day(date, tz)? else {
+            diagnostics::report(RescheduleOutOfRange {
+                date,
+                src: event.source.into(),
+                location: original.span().into(),
+            });
+            continue;
+        };
+        let (start, duration) = event.get_schedule_for_day(date);
+        let start = moved.as_ref().start.map_or(start, |s| s.0);
+        let duration = moved.as_ref().duration.map_or(duration, |d| d.0);
+        let to = moved.as_ref().to.as_ref().map(|d| *d.as_ref());
+        let Some(time) = to
+            .unwrap_or(date)
+            .and_time(start)
+            .and_local_timezone(tz)
+            .earliest()
+        else {
+            return Err(nonexistent_time(
+                event,
+                moved.span(),
+                to.unwrap_or(date),
+                tz,
+            ));
+        };
+        if now < original_time || now < time {
+            reschedule.push(output::Reschedule {
+                date,
+                to,
+                start: input::Time(start).into(),
+                duration: input::Time(duration).into(),
+                end_day: end_day(start, duration),
+                description: moved.as_ref().description.as_deref(),
+            });
+        }
+    }
+    check_reschedule_overlap(event, tz, now);
+
+    let days = match (&event.event.days, &event.event.dates) {
+        (Some(days), _) => {
+            let mut converted = convert_event_days(days, posters);
+            for weekday in WEEKDAYS {
+                if let (Some(day), Some(output)) = (days.get(weekday), converted.get_mut(weekday)) {
+                    if day.start.is_some() || day.duration.is_some() {
+                        let (start, duration) = event.get_schedule_for_weekday(weekday);
+                        output.end_day = Some(end_day(start, duration));
+                    }
+                }
             }
+            converted
+        }
+        (None, Some(_)) => output::EventDays::default(),
+        (None, None) => output::EventDays::every_day(),
+    };
+
+    for tag in &event.event.tags {
+        if !tags.contains_key(tag.as_ref().as_ref()) {
+            diagnostics::report(UnknownTag {
+                tag: tag.as_ref().to_string(),
+                src: event.source.into(),
+                location: tag.span().into(),
+            });
         }
+    }
+
+    // Events in subdirectories are categorized by the top directory.
+    let category = match event.source.path.strip_prefix(input) {
+        Ok(relative) if relative.components().nth(1).is_some() => relative
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str()),
+        _ => None,
     };
 
     Ok(output::Event {
         name,
+        category,
         start_date: event
             .event
             .start_date
+            .as_ref()
             .map(|d| {
-                d.and_time(NaiveTime::MIN)
+                d.as_ref()
+                    .and_time(NaiveTime::MIN)
                     .and_local_timezone(tz)
                     .earliest()
                     .ok_or_else(|| miette!("Midnight of start date does not exist"))
@@ -589,84 +1126,288 @@ fn prepare_event<'a, 'b>(
         end_date: event
             .event
             .end_date
+            .as_ref()
             .map(|d| {
-                d.checked_add_days(Days::new(1))
+                d.as_ref()
+                    .checked_add_days(Days::new(1))
                     .and_then(|d| d.and_time(NaiveTime::MIN).and_local_timezone(tz).earliest())
                     .ok_or_else(|| miette!("Midnight of day after end date does not exist"))
                     .map(|t| t.timestamp())
             })
             .transpose()?,
+        every: event.event.every.as_ref().map(|e| e.as_ref().0),
+        weeks,
+        dates,
+        reschedule,
         info: output::EventInfo {
             poster: poster.as_ref().and_then(|p| posters.try_get_output(p)),
             ..convert_event_info(&event.event.info, posters)
         },
         timezone: event.event.timezone.as_ref().as_ref(),
         start: event.event.start.into(),
-        duration: event.event.duration.into(),
+        duration: (*event.event.duration.as_ref()).into(),
+        end_day: end_day(event.event.start.0, event.event.duration.as_ref().0),
         platforms: &event.event.platforms,
-        days: convert_event_days(&event.event.days, posters),
+        days,
         languages,
         confirmed,
         canceled,
+        tags: event
+            .event
+            .tags
+            .iter()
+            .map(|t| t.as_ref().as_ref())
+            .collect(),
     })
 }
 
+/// Keeps the dates that haven't passed yet, reporting any dates when the event isn't held.
+fn convert_date_set(
+    event: &Event,
+    dates: &input::DateSet,
+    tz: Tz,
+    now: DateTime<Utc>,
+    out_of_range: impl Fn(NaiveDate, Range<usize>) -> Report,
+) -> Result<output::DateSet> {
+    let dates = match dates {
+        input::DateSet::All(b) => return Ok(output::DateSet::All(*b)),
+        input::DateSet::Dates(dates) => dates,
+    };
+    let mut seen = HashMap::with_capacity(dates.len());
+    let mut future = Vec::with_capacity(dates.len());
+    for date in dates {
+        if let Some(first) = seen.insert(*date.as_ref(), date.span()) {
+            diagnostics::report(DuplicateDate {
+                date: *date.as_ref(),
+                src: event.source.into(),
+                location: date.span().into(),
+                first: first.into(),
+            });
+            continue;
+        }
+        let Some(time) = event.get_time_for_day(*date.as_ref(), tz)? else {
+            diagnostics::report(out_of_range(*date.as_ref(), date.span()));
+            continue;
+        };
+        if now < time {
+            future.push(*date.as_ref());
+        }
+    }
+    Ok(if future.is_empty() {
+        output::DateSet::All(false)
+    } else {
+        output::DateSet::Dates(future)
+    })
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Gets the number of days after the start day that an event ends on. An event that ends at
+/// midnight ends on the day it started.
+fn end_day(start: NaiveTime, duration: chrono::Duration) -> u8 {
+    let end = (start - NaiveTime::MIN + duration).num_minutes();
+    ((end - 1).max(0) / (24 * 60)) as u8
+}
+
+/// Warns if a weekly event is still running when its next occurrence starts.
+fn check_overlap(event: &Event) {
+    let every = event
+        .event
+        .every
+        .as_ref()
+        .map_or(1, |e| i64::from(e.as_ref().0));
+    let occurrences: Vec<_> = WEEKDAYS
+        .into_iter()
+        .filter(|&d| match &event.event.days {
+            Some(days) => days.get(d).is_some(),
+            None => true,
+        })
+        .map(|weekday| {
+            let (start, duration) = event.get_schedule_for_weekday(weekday);
+            let start = chrono::Duration::days(weekday.num_days_from_monday().into())
+                + (start - NaiveTime::MIN);
+            (weekday, start, start + duration)
+        })
+        .collect();
+    for (i, &(weekday, _, end)) in occurrences.iter().enumerate() {
+        let next = match occurrences.get(i + 1) {
+            Some(&(_, start, _)) => start,
+            None => occurrences[0].1 + chrono::Duration::weeks(every),
+        };
+        if next < end {
+            diagnostics::report(DurationOverlap {
+                weekday,
+                src: event.source.into(),
+                location: event.event.duration.span().into(),
+            });
+            return;
+        }
+    }
+}
+
+/// Warns if an occurrence is moved to a time when the event is already being held.
+fn check_reschedule_overlap(event: &Event, tz: Tz, now: DateTime<Utc>) {
+    let dates = event.event.reschedule.iter().flat_map(|moved| {
+        let moved = moved.as_ref();
+        [Some(&moved.date), moved.to.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|d| *d.as_ref())
+    });
+    let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+        return;
+    };
+    // Time zones are less than a day from UTC, and events last up to a week.
+    let from = Utc.from_utc_datetime(&first.and_time(NaiveTime::MIN)) - chrono::Duration::days(1);
+    let until = Utc.from_utc_datetime(&last.and_time(NaiveTime::MIN)) + chrono::Duration::days(9);
+    let occurrences = occurrences::expand(event, tz, from.max(now), until);
+    for moved in occurrences.iter().filter(|o| o.reschedule.is_some()) {
+        let Some(other) = occurrences.iter().find(|o| {
+            o.reschedule != moved.reschedule
+                && o.status != occurrences::Status::Canceled
+                && o.start < moved.end
+                && moved.start < o.end
+        }) else {
+            continue;
+        };
+        if let Some(index) = moved.reschedule {
+            diagnostics::report(RescheduleOverlap {
+                date: moved.date,
+                other: other.date,
+                src: event.source.into(),
+                location: event.event.reschedule[index].span().into(),
+            });
+        }
+    }
+}
+
+fn nonexistent_time(event: &Event, span: Range<usize>, date: NaiveDate, tz: Tz) -> Report {
+    miette!(
+        labels = vec![LabeledSpan::at(span, "this date")],
+        "The event's start time on {date} does not exist in {tz}",
+    )
+    .with_source_code(NamedSource::from(event.source))
+}
+
 struct PosterInfo<'a> {
     pub source: Cow<'a, Path>,
+    /// The image encoded again without its metadata.
+    pub converted: Vec<u8>,
     pub width: u16,
     pub height: u16,
     pub hash: Output<Sha256>,
 }
 
+/// The most posters the output can have. Worlds have a URL for each poster number, so the calendar
+/// script's `Images` must have this many URLs.
+const MAX_POSTERS: usize = 4096;
+
+/// Larger images are refused instead of being scaled down, because they take too much memory.
+const MAX_SOURCE_SIZE: usize = 16384;
+
+/// The largest width and height of a thumbnail.
+const THUMBNAIL_SIZE: u32 = 256;
+
 struct Posters {
-    directory: PathBuf,
+    /// Posters are only copied when there is an output directory.
+    directory: Option<PathBuf>,
+    thumbnails: Option<PathBuf>,
     posters: Vec<state::Poster>,
-    by_sha256: HashMap<Output<Sha256>, u8>,
+    retired: Vec<state::Retired>,
+    /// The position of each poster in `posters`.
+    by_sha256: HashMap<Output<Sha256>, usize>,
+    options: input::PosterOptions,
     now: DateTime<Utc>,
+    /// The size of each poster number used by this build.
+    used: BTreeMap<u16, (u16, u16)>,
 }
 
 impl Posters {
-    fn load(directory: PathBuf, state: &State, now: DateTime<Utc>) -> Self {
+    fn load(
+        output: Option<&Path>,
+        state: &State,
+        options: input::PosterOptions,
+        now: DateTime<Utc>,
+    ) -> Self {
         let posters = state.posters.clone();
+        let retired = state.retired.clone();
         let mut by_sha256 = HashMap::with_capacity(posters.len());
         for (i, poster) in posters.iter().enumerate() {
-            by_sha256.insert(poster.sha256, i as u8);
+            by_sha256.insert(poster.sha256, i);
         }
 
-        if !directory.exists() {
-            if let Err(err) = fs::create_dir(&directory) {
-                eprintln!("{err:?}");
+        let directory = output.map(|o| o.join("posters"));
+        let thumbnails = output.map(|o| o.join("thumbnails"));
+        for directory in directory.iter().chain(&thumbnails) {
+            if !directory.exists() {
+                if let Err(err) = fs::create_dir(directory)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Could not create {}", directory.display()))
+                {
+                    diagnostics::report(err);
+                }
             }
         }
 
         Posters {
             directory,
+            thumbnails,
             posters,
+            retired,
             by_sha256,
+            options,
             now,
+            used: BTreeMap::new(),
         }
     }
 
     fn save(self, state: &mut State) {
         state.posters = self.posters;
+        state.retired = self.retired;
     }
 
     fn try_get_output(&mut self, poster: &PosterInfo<'_>) -> Option<output::PosterInfo> {
-        let index = match self.by_sha256.entry(poster.hash) {
+        let (number, saved) = match self.by_sha256.entry(poster.hash) {
             Entry::Occupied(e) => {
-                let index = *e.get();
-                self.posters[index as usize].last_used = self.now;
-                index
+                let existing = &mut self.posters[*e.get()];
+                existing.last_used = self.now;
+                (existing.number, false)
             }
             Entry::Vacant(e) => {
-                let index = if self.posters.len() < 255 {
-                    let index = self.posters.len() as u8;
+                let number = if self.posters.len() < MAX_POSTERS {
+                    let number = if self.posters.len() + self.retired.len() < MAX_POSTERS {
+                        // Low numbers are used first so that worlds built with fewer URLs keep
+                        // working for as long as possible. Numbers retired by `gc` are skipped.
+                        let used: HashSet<_> = self
+                            .posters
+                            .iter()
+                            .map(|p| p.number)
+                            .chain(self.retired.iter().map(|r| r.number))
+                            .collect();
+                        (0..).find(|n| !used.contains(n)).unwrap()
+                    } else {
+                        // Retired numbers haven't been used for longer than any poster that's
+                        // still kept, so they're replaced before the least recently used poster.
+                        let oldest = (0..self.retired.len())
+                            .min_by_key(|&i| self.retired[i].last_used)
+                            .unwrap();
+                        self.retired.swap_remove(oldest).number
+                    };
+                    e.insert(self.posters.len());
                     self.posters.push(state::Poster {
+                        number,
                         last_used: self.now,
                         sha256: poster.hash,
                     });
-                    e.insert(index);
-                    index
+                    number
                 } else {
                     let index = self
                         .posters
@@ -674,83 +1415,242 @@ impl Posters {
                         .enumerate()
                         .min_by_key(|(_, p)| p.last_used)
                         .unwrap()
-                        .0 as u8;
+                        .0;
                     e.insert(index);
-                    self.by_sha256.remove(&self.posters[index as usize].sha256);
-                    self.posters[index as usize] = state::Poster {
+                    self.by_sha256.remove(&self.posters[index].sha256);
+                    let number = self.posters[index].number;
+                    self.posters[index] = state::Poster {
+                        number,
                         last_used: self.now,
                         sha256: poster.hash,
                     };
-                    index
+                    number
                 };
-                if let Err(err) =
-                    fs::copy(&poster.source, self.directory.join(format!("{index:02x}")))
-                {
-                    eprintln!("{err:?}");
-                    return None;
+                if let Some(directory) = &self.directory {
+                    let path = directory.join(format!("{number:02x}"));
+                    if let Err(err) = fs::write(path, &poster.converted)
+                        .into_diagnostic()
+                        .wrap_err_with(|| {
+                            format!("Could not save poster {}", poster.source.display())
+                        })
+                    {
+                        diagnostics::report(err);
+                        return None;
+                    }
                 }
-                index
+                (number, true)
             }
         };
+        self.used.insert(number, (poster.width, poster.height));
         Some(output::PosterInfo {
-            number: index,
+            number,
             width: poster.width,
             height: poster.height,
+            thumbnail: self.try_get_thumbnail(number, poster, saved),
+            atlas: None,
         })
     }
-}
 
-fn try_load_poster(image_path: Cow<'_, Path>) -> Option<PosterInfo<'_>> {
-    let file = match File::open(&image_path)
-        .into_diagnostic()
-        .with_context(|| format!("Could not open {}", image_path.display()))
-    {
-        Ok(file) => file,
-        Err(e) => {
-            eprintln!("{:?}", e);
+    /// Saves a thumbnail of a poster if the poster was just saved or the thumbnail is missing.
+    /// Posters that are already small enough don't have thumbnails.
+    fn try_get_thumbnail(
+        &self,
+        number: u16,
+        poster: &PosterInfo<'_>,
+        saved: bool,
+    ) -> Option<output::Thumbnail> {
+        let (width, height) = (u32::from(poster.width), u32::from(poster.height));
+        let largest = width.max(height);
+        if largest <= THUMBNAIL_SIZE {
             return None;
         }
-    };
-    let mut reader = BufReader::new(file);
-    match imagesize::reader_size(&mut reader)
-        .map_err(|e| miette!(e))
-        .wrap_err_with(|| format!("Image {} could not be processed.", image_path.display()))
-    {
-        Ok(size) => {
-            if size.width > 2048 || size.height > 2048 {
-                eprintln!(
-                    "{:?}",
-                    Report::new(ImageTooLarge {
-                        path: image_path.to_path_buf(),
-                        width: size.width,
-                        height: size.height,
-                    }),
-                );
-                None
-            } else {
-                let mut hasher = Sha256::new();
-                match reader
-                    .seek(SeekFrom::Start(0))
-                    .and_then(|_| io::copy(&mut reader, &mut hasher))
-                    .into_diagnostic()
-                    .wrap_err_with(|| format!("Could not read {}", image_path.display()))
+        let scale = |size: u32| ((size * THUMBNAIL_SIZE + largest / 2) / largest).max(1);
+        let (width, height) = (scale(width), scale(height));
+
+        if let Some((directory, thumbnails)) = self.directory.as_ref().zip(self.thumbnails.as_ref())
+        {
+            let name = format!("{number:02x}");
+            let path = thumbnails.join(&name);
+            if saved || !path.exists() {
+                if let Err(e) = self
+                    .save_thumbnail(&directory.join(&name), &path, width, height)
+                    .wrap_err_with(|| {
+                        format!("Could not save a thumbnail of {}", poster.source.display())
+                    })
                 {
-                    Ok(_) => Some(PosterInfo {
-                        source: image_path,
-                        width: size.width as u16,
-                        height: size.height as u16,
-                        hash: hasher.finalize(),
-                    }),
-                    Err(e) => {
-                        eprintln!("{:?}", e);
-                        None
-                    }
+                    diagnostics::report(e);
+                    return None;
                 }
             }
         }
-        Err(error) => {
-            eprintln!("{error:?}");
-            None
+        Some(output::Thumbnail {
+            width: width as u16,
+            height: height as u16,
+        })
+    }
+
+    /// Thumbnails are JPEG to keep them small, unless the poster is transparent.
+    fn save_thumbnail(
+        &self,
+        poster: &Path,
+        path: &Path,
+        width: u32,
+        height: u32,
+    ) -> miette::Result<()> {
+        let image = image::io::Reader::open(poster)
+            .and_then(|r| r.with_guessed_format())
+            .into_diagnostic()?
+            .decode()
+            .into_diagnostic()?
+            .resize_exact(width, height, FilterType::Lanczos3);
+        let mut encoded = Cursor::new(Vec::new());
+        if image.color().has_alpha() {
+            image.write_to(&mut encoded, ImageOutputFormat::Png)
+        } else {
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+                &mut encoded,
+                ImageOutputFormat::Jpeg(self.options.quality.0),
+            )
+        }
+        .into_diagnostic()?;
+        fs::write(path, encoded.into_inner()).into_diagnostic()
+    }
+
+    /// Reads a poster, scaling it down if needed and converting it so it has no metadata.
+    fn try_load<'a>(&self, image_path: Cow<'a, Path>) -> Option<PosterInfo<'a>> {
+        let file = match File::open(&image_path)
+            .into_diagnostic()
+            .with_context(|| format!("Could not open {}", image_path.display()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                diagnostics::report(e);
+                return None;
+            }
+        };
+        let mut reader = BufReader::new(file);
+        let size = match imagesize::reader_size(&mut reader)
+            .map_err(|e| miette!(e))
+            .wrap_err_with(|| format!("Image {} could not be processed.", image_path.display()))
+        {
+            Ok(size) => size,
+            Err(error) => {
+                diagnostics::report(error);
+                return None;
+            }
+        };
+        if size.width > MAX_SOURCE_SIZE || size.height > MAX_SOURCE_SIZE {
+            diagnostics::report(ImageTooLarge {
+                path: image_path.to_path_buf(),
+                width: size.width,
+                height: size.height,
+            });
+            return None;
+        }
+
+        match self
+            .convert(&image_path, reader)
+            .wrap_err_with(|| format!("Image {} could not be converted.", image_path.display()))
+        {
+            Ok((image, converted)) => Some(PosterInfo {
+                width: image.width() as u16,
+                height: image.height() as u16,
+                // The hash is of the converted image so unchanged posters keep their numbers.
+                hash: Sha256::digest(&converted),
+                source: image_path,
+                converted,
+            }),
+            Err(e) => {
+                diagnostics::report(e);
+                None
+            }
+        }
+    }
+
+    /// Scales an image down to the maximum size and encodes it in the configured format. Only the
+    /// pixels are kept, so metadata is removed.
+    fn convert(
+        &self,
+        image_path: &Path,
+        mut reader: BufReader<File>,
+    ) -> miette::Result<(DynamicImage, Vec<u8>)> {
+        let mut data = Vec::new();
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_to_end(&mut data))
+            .into_diagnostic()?;
+        let reader = image::io::Reader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .into_diagnostic()?;
+        let original_format = reader.format();
+        let mut image = reader.decode().into_diagnostic()?;
+
+        let max_size = self.options.max_size.0;
+        if image.width() > max_size || image.height() > max_size {
+            image = image.resize(max_size, max_size, FilterType::Lanczos3);
+        } else if self.options.format.is_none() && original_format == Some(ImageFormat::Jpeg) {
+            // Encoding a JPEG again loses quality, so only the metadata is removed.
+            if let Some(stripped) = strip_jpeg_metadata(&data) {
+                return Ok((image, stripped));
+            }
+        }
+
+        // WebP posters are converted to PNG because WebP can't be encoded without libwebp.
+        let format = self.options.format.unwrap_or(match original_format {
+            Some(ImageFormat::Jpeg) => input::PosterFormat::Jpeg,
+            _ => input::PosterFormat::Png,
+        });
+        let mut converted = Cursor::new(Vec::new());
+        match format {
+            input::PosterFormat::Png => image.write_to(&mut converted, ImageOutputFormat::Png),
+            input::PosterFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+                &mut converted,
+                ImageOutputFormat::Jpeg(self.options.quality.0),
+            ),
+        }
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not encode {}", image_path.display()))?;
+        Ok((image, converted.into_inner()))
+    }
+}
+
+/// Removes EXIF, XMP, IPTC and comment segments from a JPEG without decoding it. JFIF, ICC profile
+/// and Adobe segments are kept because they change how the image looks.
+fn strip_jpeg_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = data.get(..2).filter(|soi| *soi == [0xff, 0xd8])?.to_vec();
+    let mut position = 2;
+    loop {
+        // Markers can be padded with extra 0xff bytes.
+        while data.get(position..position + 2) == Some(&[0xff, 0xff]) {
+            position += 1;
+        }
+        if *data.get(position)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(position + 1)?;
+        match marker {
+            // The compressed data starts at SOS and is copied with the rest of the file.
+            0xda => {
+                stripped.extend_from_slice(&data[position..]);
+                return Some(stripped);
+            }
+            0xd9 => {
+                stripped.extend_from_slice(&data[position..position + 2]);
+                return Some(stripped);
+            }
+            0x01 | 0xd0..=0xd7 => {
+                stripped.extend_from_slice(&data[position..position + 2]);
+                position += 2;
+            }
+            _ => {
+                let length = data.get(position + 2..position + 4)?;
+                let end = position + 2 + usize::from(u16::from_be_bytes([length[0], length[1]]));
+                let segment = data.get(position..end)?;
+                if !matches!(marker, 0xe1 | 0xe3..=0xed | 0xef | 0xfe) {
+                    stripped.extend_from_slice(segment);
+                }
+                position = end;
+            }
         }
     }
 }
@@ -799,6 +1699,7 @@ fn convert_event_day<'a>(
         name: value.info.name.as_deref(),
         start: value.start.map(Into::into),
         duration: value.duration.map(Into::into),
+        end_day: None,
         info: convert_event_info(&value.info, posters),
     }
 }
@@ -811,7 +1712,7 @@ fn convert_event_info<'a>(
         poster: value
             .poster
             .as_deref()
-            .and_then(|p| try_load_poster(Cow::Borrowed(Path::new(p))))
+            .and_then(|p| posters.try_load(Cow::Borrowed(Path::new(p))))
             .and_then(|p| posters.try_get_output(&p)),
         description: value.description.as_deref(),
         web: value.web.as_deref(),
@@ -821,7 +1722,6 @@ fn convert_event_info<'a>(
         twitter: value.twitter.as_deref(),
         join: &value.join,
         world: value.world.as_ref(),
-        weeks: value.weeks.as_deref(),
     }
 }
 
@@ -841,18 +1741,15 @@ fn guess_poster(event: &Event, files: &BTreeSet<PathBuf>) -> Option<PathBuf> {
         };
         image_path.set_extension(extension);
         if files.contains(&image_path) {
-            eprintln!(
-                "{:?}",
-                Report::new(MultiplePosters {
-                    found: found.clone(),
-                    extra: image_path.clone(),
-                })
-            )
+            diagnostics::report(MultiplePosters {
+                found: found.clone(),
+                extra: image_path.clone(),
+            })
         }
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct User<'a> {
     #[serde(borrow)]
@@ -861,7 +1758,7 @@ pub struct User<'a> {
     pub id: Cow<'a, str>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct World<'a> {
     #[serde(borrow)]
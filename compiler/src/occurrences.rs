@@ -0,0 +1,346 @@
+//! Expanding events into the times they're actually held, following the same rules as the calendar
+//! script.
+
+use std::{collections::BTreeMap, io::Write, path::Path, str::FromStr};
+
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+
+use crate::{
+    input::DateSet,
+    output::{self, Details},
+    safely_save, Event,
+};
+
+/// A single time an event is held.
+pub struct Occurrence {
+    /// The date the occurrence was scheduled for in the event's time zone, before it was moved.
+    pub date: NaiveDate,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub status: Status,
+    /// The index of the reschedule that moved this occurrence, if it was moved.
+    pub reschedule: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Confirmed,
+    /// The event only has some confirmed dates, and this isn't one of them.
+    Unconfirmed,
+    Canceled,
+}
+
+/// Gets the occurrences of an event that are still running at `from` or start before `until`, in
+/// order of their start times.
+///
+/// Local times that don't exist because of daylight saving time are skipped, like the calendar
+/// script does. Local times that happen twice use the earlier time.
+pub fn expand(event: &Event, tz: Tz, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Occurrence> {
+    let reschedule = &event.event.reschedule;
+    // Only the first reschedule for a date is used. The others are reported as duplicates.
+    let first_reschedule = |date: NaiveDate| {
+        reschedule
+            .iter()
+            .position(|r| *r.as_ref().date.as_ref() == date)
+    };
+
+    // Events can last up to a week, so earlier occurrences might still be running.
+    let first = from.with_timezone(&tz).date_naive() - Days::new(8);
+    let last = until.with_timezone(&tz).date_naive() + Days::new(1);
+    let mut occurrences = Vec::new();
+    for date in first.iter_days().take_while(|&d| d <= last) {
+        for (index, moved) in reschedule.iter().enumerate() {
+            let moved = moved.as_ref();
+            let original = *moved.date.as_ref();
+            if moved.to.as_ref().map_or(original, |d| *d.as_ref()) != date
+                || first_reschedule(original) != Some(index)
+                || !matches!(event.get_time_for_day(original, tz), Ok(Some(_)))
+            {
+                continue;
+            }
+            let (start, duration) = event.get_schedule_for_day(original);
+            let start = moved.start.map_or(start, |s| s.0);
+            let duration = moved.duration.map_or(duration, |d| d.0);
+            if let Some(start) = date.and_time(start).and_local_timezone(tz).earliest() {
+                occurrences.push(Occurrence {
+                    date: original,
+                    start: start.with_timezone(&Utc),
+                    end: start.with_timezone(&Utc) + duration,
+                    status: Status::Confirmed,
+                    reschedule: Some(index),
+                });
+            }
+        }
+
+        if first_reschedule(date).is_some() {
+            continue;
+        }
+        let Ok(Some(start)) = event.get_time_for_day(date, tz) else {
+            continue;
+        };
+        let (_, duration) = event.get_schedule_for_day(date);
+        occurrences.push(Occurrence {
+            date,
+            start: start.with_timezone(&Utc),
+            end: start.with_timezone(&Utc) + duration,
+            status: status(event, date),
+            reschedule: None,
+        });
+    }
+
+    occurrences.retain(|o| from < o.end && o.start < until);
+    occurrences.sort_by_key(|o| o.start);
+    occurrences
+}
+
+/// Explicitly confirmed dates can't be canceled, but canceled dates take priority when every date
+/// is confirmed.
+pub fn status(event: &Event, date: NaiveDate) -> Status {
+    let contains = |dates: &DateSet| match dates {
+        DateSet::All(all) => *all,
+        DateSet::Dates(dates) => dates.iter().any(|d| *d.as_ref() == date),
+    };
+    if matches!(&event.event.confirmed, DateSet::Dates(_)) && contains(&event.event.confirmed) {
+        Status::Confirmed
+    } else if contains(&event.event.canceled) {
+        Status::Canceled
+    } else if contains(&event.event.confirmed) {
+        Status::Confirmed
+    } else {
+        Status::Unconfirmed
+    }
+}
+
+/// Saves `occurrences.json` with the occurrences in the next number of weeks.
+pub fn save(
+    output: &Path,
+    events: &[(&Event, &output::Event)],
+    now: DateTime<Utc>,
+    weeks: u32,
+) -> miette::Result<()> {
+    let until = now + Duration::weeks(weeks.into());
+    let mut occurrences = Vec::new();
+    for (index, &(event, output)) in events.iter().enumerate() {
+        let Ok(tz) = Tz::from_str(output.timezone) else {
+            continue;
+        };
+        for occurrence in expand(event, tz, now, until) {
+            let description = occurrence
+                .reschedule
+                .and_then(|i| event.event.reschedule[i].as_ref().description.as_deref());
+            let details = |language| {
+                let details = Details::new(output, language, Some(occurrence.date.weekday()));
+                output::OccurrenceLanguage {
+                    name: details.name,
+                    description: description.or(details.description),
+                }
+            };
+            let default = details(None);
+            occurrences.push(output::Occurrence {
+                event: index,
+                date: occurrence.date,
+                start: occurrence.start.timestamp(),
+                end: occurrence.end.timestamp(),
+                status: occurrence.status,
+                languages: output
+                    .languages
+                    .keys()
+                    .map(|&language| (language, details(Some(language))))
+                    .filter(|(_, details)| *details != default)
+                    .collect::<BTreeMap<_, _>>(),
+                name: default.name,
+                description: default.description,
+            });
+        }
+    }
+    occurrences.sort_by_key(|o| (o.start, o.event));
+
+    safely_save(output, "occurrences.json", |mut t| {
+        serde_json::to_writer(
+            &mut t,
+            &output::Occurrences {
+                compiled_time: now.timestamp(),
+                until: until.timestamp(),
+                occurrences,
+            },
+        )
+        .into_diagnostic()?;
+        t.write_all(b"\n").into_diagnostic()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::TimeZone;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{input, EventFile};
+
+    /// Expands an event file between two UTC dates.
+    fn occurrences(
+        content: &str,
+        from: (i32, u32, u32),
+        until: (i32, u32, u32),
+    ) -> Vec<Occurrence> {
+        let file = EventFile {
+            path: Path::new("test.toml"),
+            content: Arc::new(content.to_owned()),
+        };
+        let event = Event {
+            source: &file,
+            base: None,
+            event: input::Event::deserialize(toml::Deserializer::new(&file.content)).unwrap(),
+        };
+        let tz = event.event.timezone.as_ref().parse().unwrap();
+        let utc = |(y, m, d)| Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+        expand(&event, tz, utc(from), utc(until))
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn skips_times_in_the_spring_forward_gap() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "America/New_York"
+            start = "02:30"
+            duration = "1:00"
+            "#,
+            (2024, 3, 9),
+            (2024, 3, 12),
+        );
+        let starts: Vec<_> = occurrences.iter().map(|o| (o.date, o.start)).collect();
+        assert_eq!(
+            starts,
+            [
+                (date(2024, 3, 9), utc(2024, 3, 9, 7, 30)),
+                (date(2024, 3, 11), utc(2024, 3, 11, 6, 30)),
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_the_earlier_fall_back_time() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "America/New_York"
+            start = "01:30"
+            duration = "1:00"
+            [days.sunday]
+            "#,
+            (2024, 11, 1),
+            (2024, 11, 5),
+        );
+        assert_eq!(occurrences.len(), 1);
+        assert_eq!(occurrences[0].start, utc(2024, 11, 3, 5, 30));
+        assert_eq!(occurrences[0].end, utc(2024, 11, 3, 6, 30));
+    }
+
+    #[test]
+    fn weeks_of_the_month_follow_local_time() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "America/New_York"
+            start = "12:00"
+            duration = "2:00"
+            weeks = [2]
+            [days.sunday]
+            "#,
+            (2024, 2, 1),
+            (2024, 5, 1),
+        );
+        let starts: Vec<_> = occurrences.iter().map(|o| o.start).collect();
+        assert_eq!(
+            starts,
+            [
+                utc(2024, 2, 11, 17, 0),
+                utc(2024, 3, 10, 16, 0),
+                utc(2024, 4, 14, 16, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn confirmed_dates_override_cancellations() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "Asia/Tokyo"
+            start = "21:00"
+            duration = "1:00"
+            confirmed = ["2024-01-08"]
+            canceled = ["2024-01-08", "2024-01-15"]
+            [days.monday]
+            "#,
+            (2024, 1, 2),
+            (2024, 1, 29),
+        );
+        let statuses: Vec<_> = occurrences.iter().map(|o| (o.date, o.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                (date(2024, 1, 8), Status::Confirmed),
+                (date(2024, 1, 15), Status::Canceled),
+                (date(2024, 1, 22), Status::Unconfirmed),
+            ]
+        );
+    }
+
+    #[test]
+    fn cancellations_override_confirming_every_date() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "Asia/Tokyo"
+            start = "21:00"
+            duration = "1:00"
+            canceled = ["2024-01-15"]
+            [days.monday]
+            "#,
+            (2024, 1, 2),
+            (2024, 1, 22),
+        );
+        let statuses: Vec<_> = occurrences.iter().map(|o| (o.date, o.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                (date(2024, 1, 8), Status::Confirmed),
+                (date(2024, 1, 15), Status::Canceled),
+            ]
+        );
+    }
+
+    #[test]
+    fn rescheduled_occurrences_replace_the_original() {
+        let occurrences = occurrences(
+            r#"
+            timezone = "Asia/Tokyo"
+            start = "21:00"
+            duration = "1:00"
+            [days.monday]
+            [[reschedule]]
+            date = "2024-01-15"
+            to = "2024-01-16"
+            start = "20:00"
+            "#,
+            (2024, 1, 14),
+            (2024, 1, 21),
+        );
+        assert_eq!(occurrences.len(), 1);
+        assert_eq!(occurrences[0].date, date(2024, 1, 15));
+        assert_eq!(occurrences[0].start, utc(2024, 1, 16, 11, 0));
+        assert_eq!(occurrences[0].status, Status::Confirmed);
+        assert_eq!(occurrences[0].reschedule, Some(0));
+    }
+}
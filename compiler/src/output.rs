@@ -1,9 +1,9 @@
 use std::{borrow::Cow, collections::BTreeMap};
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, Weekday};
 use serde::Serialize;
 
-use crate::{Language, Platform, User, World};
+use crate::{input::Week, occurrences::Status, Language, Platform, User, World};
 
 #[derive(Serialize)]
 pub struct Data<'a> {
@@ -15,16 +15,30 @@ pub struct Data<'a> {
 #[derive(Serialize)]
 pub struct Event<'a> {
     pub name: Cow<'a, str>,
+    /// The directory the event is in, if it's not at the top of the input directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_date: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_date: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub every: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weeks: Option<Vec<Week>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dates: Option<Vec<SpecialDate>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reschedule: Vec<Reschedule<'a>>,
     #[serde(flatten)]
     pub info: EventInfo<'a>,
     #[serde(rename = "tz")]
     pub timezone: &'a str,
     pub start: Minutes,
     pub duration: Minutes,
+    /// The number of days after the start day that the event ends on.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub end_day: u8,
     pub platforms: &'a [Platform],
     #[serde(flatten)]
     pub days: EventDays<'a>,
@@ -34,9 +48,65 @@ pub struct Event<'a> {
     pub canceled: DateSet,
     #[serde(skip_serializing_if = "DateSet::is_all")]
     pub confirmed: DateSet,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<&'a str>,
 }
 
-#[derive(Serialize)]
+/// The details of an event that can be different for each language and day.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Details<'a> {
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+    pub web: Option<&'a str>,
+    pub world: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    /// Gets every poster of the event, including the posters for each language and day.
+    pub fn posters_mut(&mut self) -> Vec<&mut PosterInfo> {
+        let languages = self.languages.values_mut().flat_map(|l| {
+            std::iter::once(&mut l.info).chain(l.days.iter_mut().map(|d| &mut d.info))
+        });
+        std::iter::once(&mut self.info)
+            .chain(self.days.iter_mut().map(|d| &mut d.info))
+            .chain(languages)
+            .filter_map(|info| info.poster.as_mut())
+            .collect()
+    }
+}
+
+impl<'a> Details<'a> {
+    /// Looks up each detail in the same order as the calendar script: the language's day, the day,
+    /// the language, and then the event.
+    pub fn new(event: &'a Event, language: Option<Language>, weekday: Option<Weekday>) -> Self {
+        let language = language.and_then(|l| event.languages.get(&l));
+        let layers: Vec<(Option<&str>, &EventInfo)> = [
+            language
+                .zip(weekday)
+                .and_then(|(l, d)| l.days.get(d))
+                .map(|d| (d.name, &d.info)),
+            weekday
+                .and_then(|d| event.days.get(d))
+                .map(|d| (d.name, &d.info)),
+            language.map(|l| (l.name, &l.info)),
+            Some((Some(&event.name), &event.info)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        Details {
+            name: layers.iter().find_map(|l| l.0).unwrap_or(&event.name),
+            description: layers.iter().find_map(|l| l.1.description),
+            web: layers.iter().find_map(|l| l.1.web),
+            world: layers
+                .iter()
+                .find_map(|l| l.1.world)
+                .map(|w| w.name.as_ref()),
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
 pub struct EventDays<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub monday: Option<EventDay<'a>>,
@@ -54,7 +124,59 @@ pub struct EventDays<'a> {
     pub sunday: Option<EventDay<'a>>,
 }
 
-#[derive(Serialize)]
+impl<'a> EventDays<'a> {
+    pub fn every_day() -> Self {
+        EventDays {
+            monday: Some(EventDay::default()),
+            tuesday: Some(EventDay::default()),
+            wednesday: Some(EventDay::default()),
+            thursday: Some(EventDay::default()),
+            friday: Some(EventDay::default()),
+            saturday: Some(EventDay::default()),
+            sunday: Some(EventDay::default()),
+        }
+    }
+
+    pub fn get(&self, weekday: Weekday) -> Option<&EventDay<'a>> {
+        match weekday {
+            Weekday::Mon => self.monday.as_ref(),
+            Weekday::Tue => self.tuesday.as_ref(),
+            Weekday::Wed => self.wednesday.as_ref(),
+            Weekday::Thu => self.thursday.as_ref(),
+            Weekday::Fri => self.friday.as_ref(),
+            Weekday::Sat => self.saturday.as_ref(),
+            Weekday::Sun => self.sunday.as_ref(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut EventDay<'a>> {
+        [
+            &mut self.monday,
+            &mut self.tuesday,
+            &mut self.wednesday,
+            &mut self.thursday,
+            &mut self.friday,
+            &mut self.saturday,
+            &mut self.sunday,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    pub fn get_mut(&mut self, weekday: Weekday) -> Option<&mut EventDay<'a>> {
+        match weekday {
+            Weekday::Mon => self.monday.as_mut(),
+            Weekday::Tue => self.tuesday.as_mut(),
+            Weekday::Wed => self.wednesday.as_mut(),
+            Weekday::Thu => self.thursday.as_mut(),
+            Weekday::Fri => self.friday.as_mut(),
+            Weekday::Sat => self.saturday.as_mut(),
+            Weekday::Sun => self.sunday.as_mut(),
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
 pub struct EventDay<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<&'a str>,
@@ -62,6 +184,9 @@ pub struct EventDay<'a> {
     pub start: Option<Minutes>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration: Option<Minutes>,
+    /// Only included if the start time or duration is overridden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_day: Option<u8>,
     #[serde(flatten)]
     pub info: EventInfo<'a>,
 }
@@ -79,14 +204,38 @@ pub struct EventLanguage<'a> {
 #[derive(Clone, Copy, Serialize)]
 pub struct PosterInfo {
     #[serde(rename = "n")]
-    pub number: u8,
+    pub number: u16,
     #[serde(rename = "w")]
     pub width: u16,
     #[serde(rename = "h")]
     pub height: u16,
+    /// A smaller copy of the poster, if the poster is larger than a thumbnail.
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<Thumbnail>,
+    /// Where the poster is in the atlas, if posters are packed into one.
+    #[serde(rename = "a", skip_serializing_if = "Option::is_none")]
+    pub atlas: Option<AtlasRect>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Copy, Serialize)]
+pub struct Thumbnail {
+    #[serde(rename = "w")]
+    pub width: u16,
+    #[serde(rename = "h")]
+    pub height: u16,
+}
+
+/// A poster's place in an atlas page.
+#[derive(Clone, Copy, Serialize)]
+pub struct AtlasRect {
+    #[serde(rename = "p")]
+    pub page: u8,
+    /// The x, y, width and height in texture coordinates, which start at the bottom left.
+    #[serde(rename = "r")]
+    pub uv: [f32; 4],
+}
+
+#[derive(Default, Serialize)]
 pub struct EventInfo<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub poster: Option<PosterInfo>,
@@ -104,8 +253,67 @@ pub struct EventInfo<'a> {
     pub join: &'a [User<'a>],
     #[serde(skip_serializing_if = "Option::is_none")]
     pub world: Option<&'a World<'a>>,
+    #[serde(rename = "desc", skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+pub struct SpecialDate {
+    pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<Minutes>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub weeks: Option<&'a [u8]>,
+    pub duration: Option<Minutes>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub end_day: u8,
+}
+
+/// A moved occurrence. The start time and duration are always included so that the original day's
+/// schedule doesn't need to be looked up.
+#[derive(Serialize)]
+pub struct Reschedule<'a> {
+    pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    pub start: Minutes,
+    pub duration: Minutes,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub end_day: u8,
+    #[serde(rename = "desc", skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+}
+
+/// The contents of `occurrences.json`, for consumers that don't want to expand the schedules in
+/// `data.json` themselves.
+#[derive(Serialize)]
+pub struct Occurrences<'a> {
+    #[serde(rename = "ts")]
+    pub compiled_time: i64,
+    /// Occurrences are listed until this time.
+    pub until: i64,
+    pub occurrences: Vec<Occurrence<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct Occurrence<'a> {
+    /// The index of the event in `data.json`.
+    pub event: usize,
+    /// The date the occurrence was scheduled for in the event's time zone, before it was moved.
+    pub date: NaiveDate,
+    pub start: i64,
+    pub end: i64,
+    pub status: Status,
+    pub name: &'a str,
+    #[serde(rename = "desc", skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+    /// Only languages that have a different name or description are included.
+    #[serde(rename = "lang", skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<Language, OccurrenceLanguage<'a>>,
+}
+
+#[derive(PartialEq, Serialize)]
+pub struct OccurrenceLanguage<'a> {
+    pub name: &'a str,
     #[serde(rename = "desc", skip_serializing_if = "Option::is_none")]
     pub description: Option<&'a str>,
 }
@@ -152,6 +360,22 @@ pub struct Meta<'a> {
     pub compiled_time: i64,
     #[serde(rename = "lang", skip_serializing_if = "BTreeMap::is_empty")]
     pub languages: BTreeMap<Language, MetaLanguage<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<&'a str, Tag<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<&'a str>,
+    #[serde(rename = "lang", skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<Language, TagLanguage<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct TagLanguage<'a> {
+    pub name: &'a str,
 }
 
 #[derive(Serialize)]
@@ -174,3 +398,7 @@ pub enum Hashtag<'a> {
 #[derive(Serialize)]
 #[serde(transparent)]
 pub struct Minutes(pub i32);
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
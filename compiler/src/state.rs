@@ -3,13 +3,47 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::{digest::Output, Sha256};
 
-#[derive(Default, Deserialize, Serialize)]
+/// The current version of `state.json`. Files from before versions were added are version 0.
+const VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize)]
 pub struct State {
+    #[serde(default)]
+    pub version: u32,
     pub posters: Vec<Poster>,
+    /// The numbers of posters removed by `gc`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired: Vec<Retired>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            version: VERSION,
+            posters: Vec::new(),
+            retired: Vec::new(),
+        }
+    }
+}
+
+impl State {
+    /// Updates a state file saved by an older version of the compiler.
+    pub fn migrate(&mut self) {
+        if self.version == 0 {
+            // Poster numbers used to be their positions in the list.
+            for (number, poster) in self.posters.iter_mut().enumerate() {
+                poster.number = number as u16;
+            }
+        }
+        self.version = VERSION;
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Poster {
+    /// The number of the poster file in the output's posters directory.
+    #[serde(default)]
+    pub number: u16,
     pub last_used: DateTime<Utc>,
     #[serde(
         serialize_with = "serialize_hash",
@@ -18,6 +52,14 @@ pub struct Poster {
     pub sha256: Output<Sha256>,
 }
 
+/// A poster number that's no longer used. Worlds with old data might still show it, so it's only
+/// reused once every other number is taken.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Retired {
+    pub number: u16,
+    pub last_used: DateTime<Utc>,
+}
+
 fn serialize_hash<S>(hash: &Output<Sha256>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -11,4 +11,8 @@ event_twitter = Twitter:{" "}
 event_world = World:{" "} 
 event_join = Friend request and join:
 
+filter_search = Search
+filter_language = Language
+filter_posters = Posters
+
 about_more = Link
@@ -11,4 +11,8 @@ event_twitter = ツイッター：
 event_world = ワールド：
 event_join = フレンドにjoin：
 
+filter_search = 検索
+filter_language = 言語
+filter_posters = ポスター
+
 about_more = リンク
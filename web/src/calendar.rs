@@ -12,8 +12,13 @@ use yew::{prelude::*, suspense::use_future_with};
 use crate::{
     about::About,
     event_detail::EventDetail,
-    render::{use_rendered, EventOccurrence, MinuteTime, RenderConfig, RenderParams},
-    time_slot::TimeSlot,
+    filter::Filter,
+    filter_bar::FilterBar,
+    render::{
+        continuations, use_rendered, EventOccurrence, MinuteTime, RenderConfig, RenderParams,
+        TimeSlot as RenderedTimeSlot,
+    },
+    time_slot::{ContinuedSlot, TimeSlot},
     Data, Days,
 };
 
@@ -35,6 +40,11 @@ pub(super) struct CalendarProps {
 
 #[function_component]
 pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
+    let hash = use_hash();
+    let (route, filter) = Filter::from_hash(&hash);
+    let filter = Rc::new(filter);
+    let language = filter.language.clone().or_else(|| props.language.clone());
+
     let data = use_future_with(props.data_url.clone(), |url| async move {
         let response: Response =
             match JsFuture::from(window().unwrap().fetch_with_str(url.as_str())).await {
@@ -56,7 +66,7 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
     let rendered = use_rendered(RenderParams {
         data: data.0.clone(),
         config: RenderConfig {
-            language: props.language.clone(),
+            language: language.clone(),
             time_zone: props.time_zone.clone(),
         },
     })?
@@ -83,14 +93,55 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
             for event in day {
                 let color = &mut color_index[event.id as usize];
                 if color == "" {
-                    *color = props.colors[used_colors % props.colors.len()].clone();
-                    used_colors += 1;
+                    // Events use the color of their first colored tag if they have one.
+                    *color = match event
+                        .tags
+                        .iter()
+                        .find_map(|t| data.1.tags.get(t.as_str())?.color.clone())
+                    {
+                        Some(tag_color) => tag_color,
+                        None => {
+                            used_colors += 1;
+                            props.colors[(used_colors - 1) % props.colors.len()].clone()
+                        }
+                    };
                 }
             }
         }
     }
     let color_index = IArray::from(color_index);
 
+    let change_filter = {
+        let route = route.clone();
+        move |filter: Filter| replace_hash(&filter.to_hash(&route))
+    };
+    let shown: IArray<Rc<RenderedTimeSlot>> = if !filter.is_active() {
+        IArray::clone(&rendered)
+    } else {
+        rendered
+            .iter()
+            .filter_map(|s| {
+                let days = s.days.map(|d| {
+                    d.iter()
+                        .filter(|e| filter.matches(e))
+                        .cloned()
+                        .collect::<Vec<_>>()
+                });
+                days.as_ref_array::<[_]>(props.first_day_of_week)
+                    .iter()
+                    .any(|d| !d.is_empty())
+                    .then(|| Rc::new(RenderedTimeSlot { time: s.time, days }))
+            })
+            .collect::<Vec<_>>()
+            .into()
+    };
+
+    let continued = Rc::new(continuations(&shown));
+    let any_continued = continued
+        .as_ref_array::<[_]>(props.first_day_of_week)
+        .iter()
+        .any(|d| !d.is_empty());
+
     #[derive(Eq, PartialEq)]
     enum Window {
         None,
@@ -101,26 +152,27 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
     let selected = use_state_eq(|| Window::None);
     let selected_cb = {
         let selected = selected.clone();
+        let filter = filter.clone();
         move |v: SelectedEvent| {
-            let hash = format!("{}/{}", v.event.day as u8 % 7, v.event.base_name);
+            let route = format!("{}/{}", v.event.day as u8 % 7, v.event.base_name);
             selected.set(Window::Event(v));
-            set_hash(&hash);
+            set_hash(&filter.to_hash(&route));
         }
     };
     let close = {
         let selected = selected.clone();
+        let filter = filter.clone();
         move |_| {
             selected.set(Window::None);
-            set_hash("");
+            set_hash(&filter.to_hash(""));
         }
     };
 
-    let hash = use_hash();
-    if hash.is_empty() {
+    if route.is_empty() {
         selected.set(Window::None);
-    } else if &*hash == "about" {
+    } else if route == "about" {
         selected.set(Window::About);
-    } else if let Some((day, base_name)) = hash.split_once('/') {
+    } else if let Some((day, base_name)) = route.split_once('/') {
         if let Ok(day) = usize::from_str(day) {
             let day = IsoWeekday::from(day);
             if let Some(occurrence) = rendered.iter().find_map(|s| {
@@ -136,15 +188,14 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
             }) {
                 selected.set(Window::Event(occurrence));
             } else {
-                set_hash("");
+                replace_hash(&filter.to_hash(""));
             }
         }
     }
 
     let days = props.day_names.as_clone_array(props.first_day_of_week);
 
-    let name = props
-        .language
+    let name = language
         .as_deref()
         .and_then(|l| data.1.lang.get(l))
         .and_then(|m| m.title.clone())
@@ -159,8 +210,9 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
 
     let show_about = {
         let selected = selected.clone();
+        let filter = filter.clone();
         move |_| {
-            set_hash("about");
+            set_hash(&filter.to_hash("about"));
             selected.set(Window::About);
         }
     };
@@ -168,6 +220,7 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
     Ok(html! {
         <>
             <h1><a onclick={show_about}>{name}</a></h1>
+            <FilterBar metadata={data.1.clone()} language={language.clone()} filter={filter.clone()} changed={change_filter} />
             <table>
                 <thead>
                     <tr>
@@ -180,10 +233,13 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
                     </tr>
                 </thead>
                 <tbody>
+                    if any_continued {
+                        <ContinuedSlot selected={selected_cb.clone()} colors={color_index.clone()} continued={continued} first_day_of_week={props.first_day_of_week} thumbnails={filter.posters} />
+                    }
                     {
-                        rendered.iter().map(|s| {
+                        shown.iter().map(|s| {
                             let time = s.time;
-                            html!{<TimeSlot selected={selected_cb.clone()} key={time.0} colors={color_index.clone()} time_slot={s.clone()} first_day_of_week={props.first_day_of_week} />}
+                            html!{<TimeSlot selected={selected_cb.clone()} key={time.0} colors={color_index.clone()} time_slot={s.clone()} first_day_of_week={props.first_day_of_week} thumbnails={filter.posters} />}
                         }).collect::<Html>()
                     }
                 </tbody>
@@ -197,7 +253,7 @@ pub(super) fn Calendar(props: &CalendarProps) -> HtmlResult {
                     }
                     Window::About => {
                         Some(html! {
-                            <About language={props.language.clone()} metadata={data.1.clone()} close={close} />
+                            <About language={language} metadata={data.1.clone()} close={close} />
                         })
                     }
                     Window::None => {
@@ -215,16 +271,20 @@ fn set_hash(new_hash: &str) {
     }
 }
 
+/// Changes the hash without adding an entry to the history.
+fn replace_hash(new_hash: &str) {
+    if let Some(window) = window() {
+        window.location().replace(&format!("#{new_hash}")).unwrap()
+    }
+}
+
 #[hook]
 fn use_hash() -> UseStateHandle<AttrValue> {
     fn get_hash() -> AttrValue {
         let hash = window().unwrap().location().hash().unwrap();
+        // The parts of the hash are decoded separately by `Filter::from_hash`.
         let hash = hash.strip_prefix('#').unwrap_or(&hash);
-        let hash = js_sys::decode_uri_component(hash)
-            .ok()
-            .and_then(|s| s.as_string())
-            .unwrap_or_default();
-        AttrValue::from(hash)
+        AttrValue::from(hash.to_owned())
     }
     let hash = use_state_eq(get_hash);
     {
@@ -9,6 +9,12 @@ pub(super) struct EventProps {
     pub color: AttrValue,
     pub event: Rc<render::EventOccurrence>,
     pub selected: Callback<Rc<render::EventOccurrence>>,
+    /// The event started on an earlier day.
+    #[prop_or_default]
+    pub continued: bool,
+    /// Show a thumbnail of the event's poster above its name.
+    #[prop_or_default]
+    pub thumbnail: bool,
 }
 
 #[function_component]
@@ -16,8 +22,22 @@ pub(super) fn Event(props: &EventProps) -> Html {
     let selected = props.selected.clone();
     let e = props.event.clone();
     html! {
-        <li>
-            <a onclick={Callback::from(move |_| selected.emit(e.clone()))} style={format!("color: {}", props.color)}>{&props.event.name}</a>
+        <li class={classes!(props.continued.then_some("continued"))}>
+            <a onclick={Callback::from(move |_| selected.emit(e.clone()))} style={format!("color: {}", props.color)}>
+                if let Some(poster) = props.event.info.poster.filter(|_| props.thumbnail) {
+                    // Posters that are small enough don't have thumbnails.
+                    if let Some(thumbnail) = poster.thumbnail {
+                        <img class="thumbnail" loading="lazy"
+                            width={thumbnail.width.to_string()} height={thumbnail.height.to_string()}
+                            src={format!("thumbnails/{:02x}", poster.number)} />
+                    } else {
+                        <img class="thumbnail" loading="lazy"
+                            width={poster.width.to_string()} height={poster.height.to_string()}
+                            src={format!("posters/{:02x}", poster.number)} />
+                    }
+                }
+                {&props.event.name}
+            </a>
         </li>
     }
 }
@@ -0,0 +1,111 @@
+use std::collections::BTreeSet;
+
+use yew::prelude::*;
+
+use crate::render::{EventOccurrence, Platform};
+
+/// Which events are shown in the calendar, and whether they're shown with their posters.
+///
+/// The filter is kept in the URL hash after the route, like `#1/event?platform=quest&tag=music`,
+/// so filtered views can be bookmarked and shared.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub(super) struct Filter {
+    pub platforms: BTreeSet<Platform>,
+    pub tags: BTreeSet<String>,
+    pub search: String,
+    pub language: Option<AttrValue>,
+    pub posters: bool,
+}
+
+impl Filter {
+    /// Splits a URL hash into the route and the filter.
+    pub fn from_hash(hash: &str) -> (String, Self) {
+        let (route, query) = hash.split_once('?').unwrap_or((hash, ""));
+        let mut filter = Self::default();
+        for (key, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+            let value = decode(value);
+            match key {
+                "platform" => {
+                    if let Some(platform) = Platform::from_name(&value) {
+                        filter.platforms.insert(platform);
+                    }
+                }
+                "tag" => {
+                    filter.tags.insert(value);
+                }
+                "q" => filter.search = value,
+                "lang" => filter.language = Some(AttrValue::from(value)),
+                "posters" => filter.posters = value == "1",
+                _ => {}
+            }
+        }
+        (decode(route), filter)
+    }
+
+    /// Builds the URL hash for a route with this filter.
+    pub fn to_hash(&self, route: &str) -> String {
+        let mut query = Vec::new();
+        for platform in &self.platforms {
+            query.push(format!("platform={}", platform.name()));
+        }
+        for tag in &self.tags {
+            query.push(format!("tag={}", encode(tag)));
+        }
+        if !self.search.is_empty() {
+            query.push(format!("q={}", encode(&self.search)));
+        }
+        if let Some(language) = &self.language {
+            query.push(format!("lang={}", encode(language)));
+        }
+        if self.posters {
+            query.push("posters=1".to_owned());
+        }
+
+        let route = encode(route).replace("%2F", "/");
+        if query.is_empty() {
+            route
+        } else {
+            format!("{route}?{}", query.join("&"))
+        }
+    }
+
+    /// Whether the filter hides any events.
+    pub fn is_active(&self) -> bool {
+        !self.platforms.is_empty() || !self.tags.is_empty() || !self.search.trim().is_empty()
+    }
+
+    pub fn matches(&self, event: &EventOccurrence) -> bool {
+        if !self.platforms.is_empty() && !event.platforms.iter().any(|p| self.platforms.contains(p))
+        {
+            return false;
+        }
+        if !self.tags.is_empty() && !event.tags.iter().any(|t| self.tags.contains(t.as_str())) {
+            return false;
+        }
+        let search = self.search.trim().to_lowercase();
+        if !search.is_empty() {
+            let name = event.name.to_lowercase();
+            let description = event
+                .info
+                .description
+                .as_deref()
+                .unwrap_or_default()
+                .to_lowercase();
+            if !name.contains(&search) && !description.contains(&search) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn encode(value: &str) -> String {
+    String::from(js_sys::encode_uri_component(value))
+}
+
+fn decode(value: &str) -> String {
+    js_sys::decode_uri_component(value)
+        .ok()
+        .and_then(|s| s.as_string())
+        .unwrap_or_default()
+}
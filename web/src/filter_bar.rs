@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use i18n_embed_fl::fl;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{filter::Filter, render::Platform, tag_bar::TagBar, LanguageLoader, MetadataRoot};
+
+#[derive(Properties, PartialEq)]
+pub(super) struct FilterBarProps {
+    pub metadata: Rc<MetadataRoot>,
+    pub language: Option<AttrValue>,
+    pub filter: Rc<Filter>,
+    pub changed: Callback<Filter>,
+}
+
+/// Controls for showing only some of the events.
+#[function_component]
+pub(super) fn FilterBar(props: &FilterBarProps) -> Html {
+    let language_loader = use_context::<LanguageLoader>().unwrap();
+
+    let update = |f: fn(&mut Filter, String)| {
+        let filter = props.filter.clone();
+        let changed = props.changed.clone();
+        move |value: String| {
+            let mut filter = (*filter).clone();
+            f(&mut filter, value);
+            changed.emit(filter);
+        }
+    };
+
+    let search = {
+        let update = update(|filter, value| filter.search = value);
+        move |e: InputEvent| update(e.target_unchecked_into::<HtmlInputElement>().value())
+    };
+    let language = {
+        let update = update(|filter, value| {
+            filter.language = (!value.is_empty()).then(|| AttrValue::from(value))
+        });
+        move |e: Event| update(e.target_unchecked_into::<HtmlSelectElement>().value())
+    };
+
+    let toggle_tag = Callback::from(update(|filter, value| {
+        if !filter.tags.remove(&value) {
+            filter.tags.insert(value);
+        }
+    }));
+
+    let posters = {
+        let update = update(|filter, _| filter.posters = !filter.posters);
+        move |_| update(String::new())
+    };
+
+    let mut languages: Vec<_> = props.metadata.lang.keys().collect();
+    languages.sort_unstable();
+
+    html! {
+        <div class="filters">
+            <input
+                type="search"
+                placeholder={fl!(language_loader, "filter_search")}
+                value={props.filter.search.clone()}
+                oninput={search} />
+            <ul>
+                {
+                    Platform::ALL.into_iter().map(|platform| {
+                        let update = update(|filter, value| {
+                            let platform = Platform::from_name(&value).unwrap();
+                            if !filter.platforms.remove(&platform) {
+                                filter.platforms.insert(platform);
+                            }
+                        });
+                        let label = match platform {
+                            Platform::Pc => "PC",
+                            Platform::Quest => "Quest",
+                        };
+                        html! {
+                            <li key={platform.name()}>
+                                <button
+                                    class={classes!(props.filter.platforms.contains(&platform).then_some("selected"))}
+                                    onclick={move |_| update(platform.name().to_owned())}>
+                                    {label}
+                                </button>
+                            </li>
+                        }
+                    }).collect::<Html>()
+                }
+            </ul>
+            <TagBar
+                metadata={props.metadata.clone()}
+                language={props.language.clone()}
+                selected={Rc::new(props.filter.tags.clone())}
+                toggle={toggle_tag} />
+            <button
+                class={classes!(props.filter.posters.then_some("selected"))}
+                onclick={posters}>
+                {fl!(language_loader, "filter_posters")}
+            </button>
+            if !languages.is_empty() {
+                <select onchange={language}>
+                    <option value="" selected={props.filter.language.is_none()}>
+                        {fl!(language_loader, "filter_language")}
+                    </option>
+                    {
+                        languages.into_iter().map(|l| html! {
+                            <option key={l.as_str()} value={l.clone()} selected={props.filter.language.as_deref() == Some(l.as_str())}>
+                                {l}
+                            </option>
+                        }).collect::<Html>()
+                    }
+                </select>
+            }
+        </div>
+    }
+}
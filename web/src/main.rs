@@ -1,4 +1,9 @@
-use std::{collections::HashMap, ops::Deref, rc::Rc, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Deref,
+    rc::Rc,
+    str::FromStr,
+};
 
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
@@ -23,12 +28,15 @@ mod about;
 mod calendar;
 mod event;
 mod event_detail;
+mod filter;
+mod filter_bar;
 mod render;
+mod tag_bar;
 mod time_slot;
 
 type LanguageLoader = Rc<RefEqual<FluentLanguageLoader>>;
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
 struct Days<T> {
     sunday: T,
     monday: T,
@@ -40,6 +48,30 @@ struct Days<T> {
 }
 
 impl<T> Days<T> {
+    fn get_mut(&mut self, day: IsoWeekday) -> &mut T {
+        match day {
+            IsoWeekday::Monday => &mut self.monday,
+            IsoWeekday::Tuesday => &mut self.tuesday,
+            IsoWeekday::Wednesday => &mut self.wednesday,
+            IsoWeekday::Thursday => &mut self.thursday,
+            IsoWeekday::Friday => &mut self.friday,
+            IsoWeekday::Saturday => &mut self.saturday,
+            IsoWeekday::Sunday => &mut self.sunday,
+        }
+    }
+
+    fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Days<U> {
+        Days {
+            sunday: f(&self.sunday),
+            monday: f(&self.monday),
+            tuesday: f(&self.tuesday),
+            wednesday: f(&self.wednesday),
+            thursday: f(&self.thursday),
+            friday: f(&self.friday),
+            saturday: f(&self.saturday),
+        }
+    }
+
     fn as_ref_array<R>(&self, first_day_of_week: IsoWeekday) -> [&R; 7]
     where
         R: ?Sized,
@@ -86,6 +118,8 @@ struct MetadataRoot {
     #[serde(flatten)]
     meta: Metadata,
     lang: HashMap<String, Metadata>,
+    #[serde(default)]
+    tags: BTreeMap<String, Tag>,
 }
 
 #[derive(Deserialize, Eq, PartialEq)]
@@ -95,6 +129,27 @@ struct Metadata {
     link: Option<AttrValue>,
 }
 
+#[derive(Deserialize, Eq, PartialEq)]
+struct Tag {
+    name: AttrValue,
+    color: Option<AttrValue>,
+    #[serde(default)]
+    lang: HashMap<String, TagLanguage>,
+}
+
+impl Tag {
+    fn name(&self, language: Option<&str>) -> &AttrValue {
+        language
+            .and_then(|l| self.lang.get(l))
+            .map_or(&self.name, |l| &l.name)
+    }
+}
+
+#[derive(Deserialize, Eq, PartialEq)]
+struct TagLanguage {
+    name: AttrValue,
+}
+
 #[function_component]
 fn App(props: &AppProps) -> Html {
     let first_day_of_week = props.first_day_of_week;
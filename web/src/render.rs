@@ -24,12 +24,14 @@ pub(super) struct EventOccurrence {
 
     pub name: AttrValue,
     pub base_name: AttrValue,
-    pub duration: MinuteTime,
+    pub duration: MinuteDuration,
     #[serde(flatten)]
     pub info: EventInfo,
 
     pub platforms: Vec<Platform>,
     #[serde(default)]
+    pub tags: Vec<AttrValue>,
+    #[serde(default)]
     pub canceled: bool,
     #[serde(default = "default_true")]
     pub confirmed: bool,
@@ -75,14 +77,24 @@ pub(super) struct EventInfo {
     #[serde(default)]
     pub join: Vec<Named>,
     pub world: Option<Named>,
-    pub weeks: Option<Vec<u8>>,
+    pub weeks: Option<Vec<i8>>,
     pub description: Option<AttrValue>,
 }
 
 #[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
 pub(super) struct Poster {
     #[serde(rename = "n")]
-    pub number: u8,
+    pub number: u16,
+    #[serde(rename = "w")]
+    pub width: u16,
+    #[serde(rename = "h")]
+    pub height: u16,
+    #[serde(rename = "t")]
+    pub thumbnail: Option<Thumbnail>,
+}
+
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
+pub(super) struct Thumbnail {
     #[serde(rename = "w")]
     pub width: u16,
     #[serde(rename = "h")]
@@ -121,13 +133,29 @@ pub(super) struct Named {
     pub id: AttrValue,
 }
 
-#[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Copy, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
 #[serde(rename_all = "kebab-case")]
 pub(super) enum Platform {
     Pc,
     Quest,
 }
 
+impl Platform {
+    pub const ALL: [Platform; 2] = [Platform::Pc, Platform::Quest];
+
+    /// The name used in the data and in URLs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Pc => "pc",
+            Platform::Quest => "quest",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
 #[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
 pub(super) struct MinuteTime(pub u16);
 
@@ -173,6 +201,55 @@ impl ToHtml for MinuteTime {
     }
 }
 
+/// A number of minutes, which unlike [`MinuteTime`] can be longer than a day.
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(transparent)]
+pub(super) struct MinuteDuration(pub u16);
+
+impl Display for MinuteDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hours = self.0 / 60;
+        let minutes = self.0 % 60;
+        write!(f, "{hours:02}:{minutes:02}")
+    }
+}
+
+/// An event that started on an earlier day and is still running at midnight.
+#[derive(Eq, PartialEq)]
+pub(super) struct Continuation {
+    pub start: MinuteTime,
+    pub event: Rc<EventOccurrence>,
+}
+
+/// Finds the events that run past midnight, grouped by the days they continue into.
+pub(super) fn continuations(slots: &IArray<Rc<TimeSlot>>) -> Days<Vec<Rc<Continuation>>> {
+    let mut days = Days::<Vec<Rc<Continuation>>>::default();
+    for slot in slots.iter() {
+        // The column is the day in the viewer's time zone, which can differ from the event's day.
+        for (column, events) in slot
+            .days
+            .as_ref_array::<[_]>(IsoWeekday::Monday)
+            .into_iter()
+            .enumerate()
+        {
+            for event in events {
+                let end = u32::from(slot.time.0) + u32::from(event.duration.0);
+                let continuation = Rc::new(Continuation {
+                    start: slot.time,
+                    event: event.clone(),
+                });
+                // A week long event can't continue into its own day.
+                for offset in 1..=(end.saturating_sub(1) / (24 * 60)).min(6) {
+                    // IsoWeekday numbers start from 1 for Monday.
+                    days.get_mut(IsoWeekday::from(column + 1 + offset as usize))
+                        .push(continuation.clone());
+                }
+            }
+        }
+    }
+    days
+}
+
 #[wasm_bindgen]
 extern "C" {
     async fn renderData(input: &str, config: &str) -> JsValue;
@@ -0,0 +1,46 @@
+use std::{collections::BTreeSet, rc::Rc};
+
+use yew::prelude::*;
+
+use crate::MetadataRoot;
+
+#[derive(Properties, PartialEq)]
+pub(super) struct TagBarProps {
+    pub metadata: Rc<MetadataRoot>,
+    pub language: Option<AttrValue>,
+    pub selected: Rc<BTreeSet<String>>,
+    pub toggle: Callback<String>,
+}
+
+/// Buttons for showing only the events with some tags.
+#[function_component]
+pub(super) fn TagBar(props: &TagBarProps) -> Html {
+    if props.metadata.tags.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <ul class="tags">
+            {
+                props.metadata.tags.iter().map(|(id, tag)| {
+                    let toggle = props.toggle.clone();
+                    let onclick = {
+                        let id = id.clone();
+                        move |_| toggle.emit(id.clone())
+                    };
+                    let style = tag.color.as_ref().map(|c| format!("border-color: {c}"));
+                    html! {
+                        <li key={id.as_str()}>
+                            <button
+                                class={classes!(props.selected.contains(id).then_some("selected"))}
+                                style={style}
+                                onclick={onclick}>
+                                {tag.name(props.language.as_deref())}
+                            </button>
+                        </li>
+                    }
+                }).collect::<Html>()
+            }
+        </ul>
+    }
+}
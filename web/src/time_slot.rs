@@ -4,7 +4,7 @@ use icu::calendar::types::IsoWeekday;
 use implicit_clone::unsync::IArray;
 use yew::prelude::*;
 
-use crate::{calendar::SelectedEvent, event::Event, render};
+use crate::{calendar::SelectedEvent, event::Event, render, Days};
 
 #[derive(Properties, PartialEq)]
 pub(super) struct TimeSlotProps {
@@ -12,6 +12,8 @@ pub(super) struct TimeSlotProps {
     pub first_day_of_week: IsoWeekday,
     pub colors: IArray<AttrValue>,
     pub selected: Callback<SelectedEvent>,
+    #[prop_or_default]
+    pub thumbnails: bool,
 }
 
 #[function_component]
@@ -32,6 +34,7 @@ pub(super) fn TimeSlot(props: &TimeSlotProps) -> Html {
                                         html!{ <Event key={id}
                                             color={props.colors[id as usize].clone()}
                                             event={e.clone()}
+                                            thumbnail={props.thumbnails}
                                             selected={Callback::from(move |_| selected.emit(SelectedEvent { time, event: e.clone() }))} /> }
                                     }).collect::<Html>()
                                 }
@@ -43,3 +46,45 @@ pub(super) fn TimeSlot(props: &TimeSlotProps) -> Html {
         </tr>
     }
 }
+
+#[derive(Properties, PartialEq)]
+pub(super) struct ContinuedSlotProps {
+    pub continued: Rc<Days<Vec<Rc<render::Continuation>>>>,
+    pub first_day_of_week: IsoWeekday,
+    pub colors: IArray<AttrValue>,
+    pub selected: Callback<SelectedEvent>,
+    #[prop_or_default]
+    pub thumbnails: bool,
+}
+
+/// The events that are still running at midnight from an earlier day.
+#[function_component]
+pub(super) fn ContinuedSlot(props: &ContinuedSlotProps) -> Html {
+    html! {
+        <tr class="continued">
+            <th scope="row">{render::MinuteTime(0)}</th>
+            {
+                props.continued.as_ref_array::<[_]>(props.first_day_of_week).iter().enumerate().map(|(i, d)| {
+                    html! {
+                        <td key={i}>
+                            <ul>
+                                {
+                                    d.iter().cloned().map(|c| {
+                                        let selected = props.selected.clone();
+                                        let id = c.event.id;
+                                        html!{ <Event key={id}
+                                            color={props.colors[id as usize].clone()}
+                                            event={c.event.clone()}
+                                            continued={true}
+                                            thumbnail={props.thumbnails}
+                                            selected={Callback::from(move |_| selected.emit(SelectedEvent { time: c.start, event: c.event.clone() }))} /> }
+                                    }).collect::<Html>()
+                                }
+                            </ul>
+                        </td>
+                    }
+                }).collect::<Html>()
+            }
+        </tr>
+    }
+}